@@ -0,0 +1,19 @@
+//! The chess rules and search without the board, speaking UCI on stdin and stdout so GUIs and
+//! tournament managers such as cutechess-cli can run it.
+
+use chess_core::uci_server::UciServer;
+use std::io::BufRead;
+
+fn main() {
+    let mut server = UciServer::new(std::io::stdout());
+    for line in std::io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if !server.handle(&line) {
+            return;
+        }
+    }
+    // the GUI went away without saying quit
+    server.handle("quit");
+}
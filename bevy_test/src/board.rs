@@ -1,8 +1,13 @@
-use crate::pieces::{Piece, PieceColor, PieceType};
-use bevy::app::AppExit;
+use crate::engine::{EnginePlayer, EngineTask};
+use crate::pieces::{Piece, PieceColor, PieceType, SyncPiecesEvent};
+use crate::replay::Replay;
+use crate::ui::MoveInput;
 use bevy::prelude::*;
 use bevy_mod_picking::{PickableBundle, PickingCamera};
-use std::collections::{HashMap, HashSet};
+use chess_core::pgn::{pgn_date, PgnTags};
+use chess_core::{Game, GameResult, Move, Position};
+use std::collections::HashSet;
+use std::time::SystemTime;
 #[derive(Component, Debug)]
 pub struct Square {
     pub x: u8,
@@ -13,6 +18,10 @@ impl Square {
     fn is_white(&self) -> bool {
         (self.x + self.y + 1) % 2 == 0
     }
+
+    fn square(&self) -> chess_core::Square {
+        chess_core::Square::new(self.x, self.y)
+    }
 }
 
 fn create_board(
@@ -55,10 +64,57 @@ pub struct HighlightedSquares {
 }
 
 #[derive(Default, Resource)]
-struct SelectedPiece {
+pub struct SelectedPiece {
     entity: Option<Entity>,
 }
 
+/// An earlier position of the game shown on the board, which is read-only meanwhile. The game
+/// itself goes on underneath.
+#[derive(Default, Resource)]
+pub struct HistoryView {
+    /// Moves played up to the shown position.
+    ply: Option<usize>,
+}
+
+impl HistoryView {
+    pub fn is_active(&self) -> bool {
+        self.ply.is_some()
+    }
+
+    pub fn ply(&self) -> Option<usize> {
+        self.ply
+    }
+
+    /// The position the board shows, the game's own one unless an earlier one is viewed.
+    pub fn position(&self, game: &Game) -> Position {
+        self.ply
+            .and_then(|ply| game.position_at(ply))
+            .unwrap_or(*game.position())
+    }
+}
+
+/// Views the position after `Some(ply)` moves, `None` goes back to the game.
+pub struct ViewPlyEvent(pub Option<usize>);
+
+/// The game being played, the piece entities mirror its position.
+#[derive(Default, Resource, Deref, DerefMut)]
+pub struct ChessGame(pub Game);
+
+/// Asks for a move to be played on the `ChessGame`, it is checked for legality first.
+pub struct MakeMoveEvent(pub Move);
+
+/// Sent for every move that got played on the `ChessGame`.
+pub struct MoveMadeEvent(pub Move);
+
+/// Squares of a pawn move waiting on the player to pick what it promotes to.
+#[derive(Default, Resource)]
+pub struct PendingPromotion {
+    pub squares: Option<(chess_core::Square, chess_core::Square)>,
+}
+
+/// Sent once the player picked the piece type for the pending promotion.
+pub struct PromotionEvent(pub PieceType);
+
 #[derive(Resource)]
 pub struct PlayerTurn(pub PieceColor);
 impl Default for PlayerTurn {
@@ -148,7 +204,7 @@ fn select_square(
         if let Some((square_entity, _intersection)) = picking_camera.get_nearest_intersection() {
             if let Ok(_square) = squares_query.get(square_entity) {
                 // Mark it as selected
-                println!("select_square: {:?}", _square);
+                trace!("select_square: {:?}", _square);
                 selected_square.entity = Some(square_entity);
             }
         } else {
@@ -165,10 +221,21 @@ fn select_piece(
     mut selected_piece: ResMut<SelectedPiece>,
     mut highlighted_squares: ResMut<HighlightedSquares>,
     turn: Res<PlayerTurn>,
+    game: Res<ChessGame>,
+    pending_promotion: Res<PendingPromotion>,
+    replay: Res<Replay>,
+    view: Res<HistoryView>,
+    engine: Res<EnginePlayer>,
     squares_query: Query<&Square>,
     pieces_query: Query<(Entity, &Piece)>,
 ) {
-    if !selected_square.is_changed() {
+    if !selected_square.is_changed()
+        || game.result().is_some()
+        || pending_promotion.squares.is_some()
+        || replay.is_active()
+        || view.is_active()
+        || engine.plays(turn.0)
+    {
         return;
     }
 
@@ -191,13 +258,14 @@ fn select_piece(
         // Select the piece in the currently selected square
         for (piece_entity, piece) in pieces_query.iter() {
             if piece.x == square.x && piece.y == square.y && piece.color == turn.0 {
-                // high
-                let pieces_map = pieces_query
-                    .iter()
-                    .map(|(e, p)| ((p.x, p.y), p))
-                    .collect::<HashMap<(u8, u8), &Piece>>();
-                println!("Selected piece {:?}", piece);
-                highlighted_squares.entities = piece.possible_moves(pieces_map);
+                trace!("Selected piece {:?}", piece);
+                // highlight where the piece may legally go
+                highlighted_squares.entities = game
+                    .legal_moves()
+                    .into_iter()
+                    .filter(|mv| mv.from == piece.square())
+                    .map(|mv| (mv.to.rank(), mv.to.file()))
+                    .collect();
                 // piece_entity is now the entity in the same square
                 selected_piece.entity = Some(piece_entity);
                 break;
@@ -207,13 +275,14 @@ fn select_piece(
 }
 
 fn move_piece(
-    mut commands: Commands,
     selected_square: Res<SelectedSquare>,
     selected_piece: Res<SelectedPiece>,
-    highlighted_squares: ResMut<HighlightedSquares>,
-    mut turn: ResMut<PlayerTurn>,
+    highlighted_squares: Res<HighlightedSquares>,
+    game: Res<ChessGame>,
+    mut pending_promotion: ResMut<PendingPromotion>,
     squares_query: Query<&Square>,
-    mut pieces_query: Query<(Entity, &mut Piece)>,
+    pieces_query: Query<&Piece>,
+    mut make_move_event: EventWriter<MakeMoveEvent>,
     mut reset_selected_event: EventWriter<ResetSelectedEvent>,
 ) {
     if !selected_square.is_changed() {
@@ -233,42 +302,307 @@ fn move_piece(
     };
 
     if let Some(selected_piece_entity) = selected_piece.entity {
-        let pieces_entity_vec = pieces_query
-            .iter_mut()
-            .map(|(entity, piece)| (entity, *piece))
-            .collect::<Vec<(Entity, Piece)>>();
-        // Move the selected piece to the selected square
-        let mut piece =
-            if let Ok((_piece_entity, piece)) = pieces_query.get_mut(selected_piece_entity) {
+        if highlighted_squares.entities.contains(&(square.x, square.y)) {
+            let piece = if let Ok(piece) = pieces_query.get(selected_piece_entity) {
                 piece
             } else {
                 return;
             };
 
-        if highlighted_squares.entities.contains(&(square.x, square.y)) {
-            // Check if a piece of the opposite color exists in this square and despawn it
-            for (other_entity, other_piece) in pieces_entity_vec {
-                if other_piece.x == square.x
-                    && other_piece.y == square.y
-                    && other_piece.color != piece.color
-                {
-                    println!("taking piece {:?}", other_piece);
-                    // Mark the piece as taken
-                    commands.entity(other_entity).insert(Taken);
+            let (from, to) = (piece.square(), square.square());
+            let moves = game
+                .legal_moves()
+                .into_iter()
+                .filter(|mv| mv.from == from && mv.to == to)
+                .collect::<Vec<Move>>();
+            if moves.iter().any(|mv| mv.promotion.is_some()) {
+                // the move is made once the player picked a piece
+                pending_promotion.squares = Some((from, to));
+            } else if let Some(&mv) = moves.first() {
+                make_move_event.send(MakeMoveEvent(mv));
+            }
+        }
+
+        reset_selected_event.send(ResetSelectedEvent);
+    }
+}
+
+fn promote_piece(
+    game: Res<ChessGame>,
+    mut pending_promotion: ResMut<PendingPromotion>,
+    mut promotion_events: EventReader<PromotionEvent>,
+    mut make_move_event: EventWriter<MakeMoveEvent>,
+) {
+    for event in promotion_events.iter() {
+        if let Some((from, to)) = pending_promotion.squares.take() {
+            if let Some(mv) = game.position().legal_move(from, to, Some(event.0)) {
+                make_move_event.send(MakeMoveEvent(mv));
+            }
+        }
+    }
+}
+
+/// Plays the requested moves on the `ChessGame`, the pieces follow through `MoveMadeEvent`.
+pub fn make_moves(
+    mut game: ResMut<ChessGame>,
+    mut turn: ResMut<PlayerTurn>,
+    mut make_move_events: EventReader<MakeMoveEvent>,
+    mut move_made_event: EventWriter<MoveMadeEvent>,
+    mut game_result_event: EventWriter<GameResult>,
+) {
+    for event in make_move_events.iter() {
+        let position = *game.position();
+        if let Err(err) = game.make_move(event.0) {
+            warn!("{}", err);
+            continue;
+        }
+        debug!("played {}", position.san(event.0));
+        move_made_event.send(MoveMadeEvent(event.0));
+        // Change turn
+        turn.change();
+
+        if let Some(result) = game.result() {
+            debug!("game over: {:?}", result);
+            game_result_event.send(result);
+        }
+    }
+}
+
+/// Ways to end the game other than on the board, for the player whose move it is or, against
+/// the engine, the human player.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameActionEvent {
+    Resign,
+    OfferDraw,
+    AcceptDraw,
+    DeclineDraw,
+    /// Draw by threefold repetition or the fifty-move rule.
+    ClaimDraw,
+}
+
+/// Applies `GameActionEvent`s and their keys: R resigns, O offers a draw, A and N accept and
+/// decline one and D claims one.
+fn game_actions(
+    keys: Res<Input<KeyCode>>,
+    move_input: Res<MoveInput>,
+    replay: Res<Replay>,
+    engine: Res<EnginePlayer>,
+    mut action_events: EventReader<GameActionEvent>,
+    mut game: ResMut<ChessGame>,
+    mut pending_promotion: ResMut<PendingPromotion>,
+    mut highlighted_squares: ResMut<HighlightedSquares>,
+    mut game_result_event: EventWriter<GameResult>,
+    mut reset_selected_event: EventWriter<ResetSelectedEvent>,
+) {
+    let mut actions = action_events
+        .iter()
+        .copied()
+        .collect::<Vec<GameActionEvent>>();
+    if !move_input.active {
+        for (key, action) in [
+            (KeyCode::R, GameActionEvent::Resign),
+            (KeyCode::O, GameActionEvent::OfferDraw),
+            (KeyCode::A, GameActionEvent::AcceptDraw),
+            (KeyCode::N, GameActionEvent::DeclineDraw),
+            (KeyCode::D, GameActionEvent::ClaimDraw),
+        ] {
+            if keys.just_pressed(key) {
+                actions.push(action);
+            }
+        }
+    }
+    if actions.is_empty() || replay.is_active() || game.result().is_some() {
+        return;
+    }
+
+    let player = if engine.plays(game.side_to_move()) {
+        game.side_to_move().opposite()
+    } else {
+        game.side_to_move()
+    };
+    for action in actions {
+        // a player can't answer their own offer
+        let answerable = game.draw_offer().map_or(false, |color| color != player);
+        let result = match action {
+            GameActionEvent::Resign => game.resign(player),
+            GameActionEvent::OfferDraw => {
+                if game.draw_offer().is_none() && game.offer_draw(player) {
+                    debug!("{} offers a draw", player);
                 }
+                None
             }
+            GameActionEvent::AcceptDraw if answerable => game.accept_draw(),
+            GameActionEvent::DeclineDraw if answerable => {
+                game.decline_draw();
+                debug!("{} declines the draw", player);
+                None
+            }
+            GameActionEvent::ClaimDraw => game.claim_draw(),
+            _ => None,
+        };
+        if let Some(result) = result {
+            debug!("game over: {:?}", result);
+            pending_promotion.squares = None;
+            highlighted_squares.entities.clear();
+            reset_selected_event.send(ResetSelectedEvent);
+            game_result_event.send(result);
+            break;
+        }
+    }
+}
+
+/// Prints the current position as FEN when F is pressed.
+fn export_fen(keys: Res<Input<KeyCode>>, move_input: Res<MoveInput>, game: Res<ChessGame>) {
+    if keys.just_pressed(KeyCode::F) && !move_input.active {
+        println!("{}", game.position().to_fen());
+    }
+}
+
+/// Where `export_pgn` writes the game.
+const PGN_FILE: &str = "game.pgn";
 
-            // Move piece
-            piece.x = square.x;
-            piece.y = square.y;
+/// Writes the game as PGN when P is pressed and once it is over.
+fn export_pgn(
+    keys: Res<Input<KeyCode>>,
+    move_input: Res<MoveInput>,
+    game: Res<ChessGame>,
+    mut game_result_events: EventReader<GameResult>,
+) {
+    let game_over = game_result_events.iter().count() > 0;
+    let requested = keys.just_pressed(KeyCode::P) && !move_input.active;
+    if !requested && !game_over {
+        return;
+    }
+
+    let tags = PgnTags {
+        event: "bevy_test".to_string(),
+        date: pgn_date(SystemTime::now()),
+        ..PgnTags::default()
+    };
+    match std::fs::write(PGN_FILE, game.to_pgn(&tags)) {
+        Ok(()) => println!("game written to {}", PGN_FILE),
+        Err(err) => println!("couldn't write {}: {}", PGN_FILE, err),
+    }
+}
 
-            println!("moving piece {:?}", piece);
-            // Change turn
-            turn.change();
+/// Takes back the last move or plays a taken back move again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryEvent {
+    Undo,
+    Redo,
+}
+
+/// Applies `HistoryEvent`s and the Ctrl+Z / Ctrl+Y shortcuts, the pieces are then rearranged
+/// to the restored position.
+fn undo_redo(
+    keys: Res<Input<KeyCode>>,
+    move_input: Res<MoveInput>,
+    replay: Res<Replay>,
+    engine: Res<EnginePlayer>,
+    mut history_events: EventReader<HistoryEvent>,
+    mut game: ResMut<ChessGame>,
+    mut engine_task: ResMut<EngineTask>,
+    mut turn: ResMut<PlayerTurn>,
+    mut view: ResMut<HistoryView>,
+    mut pending_promotion: ResMut<PendingPromotion>,
+    mut highlighted_squares: ResMut<HighlightedSquares>,
+    mut sync_event: EventWriter<SyncPiecesEvent>,
+    mut reset_selected_event: EventWriter<ResetSelectedEvent>,
+) {
+    let mut requests = history_events
+        .iter()
+        .copied()
+        .collect::<Vec<HistoryEvent>>();
+    let control = keys.pressed(KeyCode::LControl) || keys.pressed(KeyCode::RControl);
+    if control && !move_input.active {
+        if keys.just_pressed(KeyCode::Z) {
+            requests.push(HistoryEvent::Undo);
+        }
+        if keys.just_pressed(KeyCode::Y) {
+            requests.push(HistoryEvent::Redo);
         }
+    }
+    if requests.is_empty() || replay.is_active() {
+        return;
+    }
 
-        reset_selected_event.send(ResetSelectedEvent);
+    let mut changed = false;
+    for request in requests {
+        // against the engine steps go from one of the player's moves to the next, stopping on
+        // the engine's move would only have it play the same reply again
+        loop {
+            let mv = match request {
+                HistoryEvent::Undo => game.undo(),
+                HistoryEvent::Redo => game.redo(),
+            };
+            changed |= mv.is_some();
+            if mv.is_none() || !engine.plays(game.side_to_move()) {
+                break;
+            }
+        }
+    }
+    if !changed {
+        return;
+    }
+    engine_task.discard();
+    turn.0 = game.side_to_move();
+    view.ply = None;
+    pending_promotion.squares = None;
+    highlighted_squares.entities.clear();
+    reset_selected_event.send(ResetSelectedEvent);
+    sync_event.send(SyncPiecesEvent);
+}
+
+/// Applies `ViewPlyEvent`s, the pieces are rearranged to the viewed position.
+fn view_history(
+    replay: Res<Replay>,
+    game: Res<ChessGame>,
+    mut view_events: EventReader<ViewPlyEvent>,
+    mut view: ResMut<HistoryView>,
+    mut highlighted_squares: ResMut<HighlightedSquares>,
+    mut sync_event: EventWriter<SyncPiecesEvent>,
+    mut reset_selected_event: EventWriter<ResetSelectedEvent>,
+) {
+    let Some(event) = view_events.iter().last() else {
+        return;
+    };
+    // the latest position is the game itself
+    let ply = event.0.filter(|&ply| ply < game.moves().count());
+    if replay.is_active() || ply == view.ply {
+        return;
     }
+    view.ply = ply;
+    highlighted_squares.entities.clear();
+    reset_selected_event.send(ResetSelectedEvent);
+    sync_event.send(SyncPiecesEvent);
+}
+
+/// Starts over from the position the game started from, with a fresh set of pieces.
+pub struct NewGameEvent;
+
+pub fn new_game(
+    mut new_game_events: EventReader<NewGameEvent>,
+    mut game: ResMut<ChessGame>,
+    mut engine_task: ResMut<EngineTask>,
+    mut turn: ResMut<PlayerTurn>,
+    mut view: ResMut<HistoryView>,
+    mut selected_square: ResMut<SelectedSquare>,
+    mut selected_piece: ResMut<SelectedPiece>,
+    mut highlighted_squares: ResMut<HighlightedSquares>,
+    mut pending_promotion: ResMut<PendingPromotion>,
+) {
+    if new_game_events.iter().count() == 0 {
+        return;
+    }
+    game.0 = Game::new(game.initial_position());
+    engine_task.discard();
+    turn.0 = game.side_to_move();
+    view.ply = None;
+    selected_square.entity = None;
+    selected_piece.entity = None;
+    highlighted_squares.entities.clear();
+    pending_promotion.squares = None;
+    debug!("new game");
 }
 
 struct ResetSelectedEvent;
@@ -284,31 +618,6 @@ fn reset_selected(
     }
 }
 
-#[derive(Component)]
-struct Taken;
-fn despawn_taken_pieces(
-    mut commands: Commands,
-    mut app_exit_events: EventWriter<AppExit>,
-    query: Query<(Entity, &Piece, &Taken)>,
-) {
-    for (entity, piece, _taken) in query.iter() {
-        // If the king is taken, we should exit
-        if piece.piece_type == PieceType::King {
-            println!(
-                "{} won! Thanks for playing!",
-                match piece.color {
-                    PieceColor::White => "Black",
-                    PieceColor::Black => "White",
-                }
-            );
-            app_exit_events.send(AppExit);
-        }
-
-        // Despawn piece and children
-        commands.entity(entity).despawn_recursive();
-    }
-}
-
 pub struct BoardPlugin;
 
 impl Plugin for BoardPlugin {
@@ -318,7 +627,18 @@ impl Plugin for BoardPlugin {
             .init_resource::<SelectedPiece>()
             .init_resource::<SquareMaterials>()
             .init_resource::<PlayerTurn>()
+            .init_resource::<ChessGame>()
+            .init_resource::<PendingPromotion>()
+            .init_resource::<HistoryView>()
             .add_event::<ResetSelectedEvent>()
+            .add_event::<MakeMoveEvent>()
+            .add_event::<MoveMadeEvent>()
+            .add_event::<GameResult>()
+            .add_event::<PromotionEvent>()
+            .add_event::<HistoryEvent>()
+            .add_event::<GameActionEvent>()
+            .add_event::<NewGameEvent>()
+            .add_event::<ViewPlyEvent>()
             .add_startup_system(create_board)
             .add_system_to_stage(CoreStage::PostUpdate, color_squares)
             .add_system_to_stage(CoreStage::PostUpdate, select_square)
@@ -327,10 +647,17 @@ impl Plugin for BoardPlugin {
                 move_piece.after(select_square).before(select_piece),
             )
             .add_system_to_stage(CoreStage::PostUpdate, select_piece.after(select_square))
+            .add_system_to_stage(CoreStage::PostUpdate, reset_selected.after(select_square))
+            .add_system_to_stage(CoreStage::PostUpdate, promote_piece)
             .add_system_to_stage(
                 CoreStage::PostUpdate,
-                despawn_taken_pieces.after(move_piece),
+                make_moves.after(move_piece).after(promote_piece),
             )
-            .add_system_to_stage(CoreStage::PostUpdate, reset_selected.after(select_square));
+            .add_system(export_fen)
+            .add_system_to_stage(CoreStage::PostUpdate, game_actions.before(export_pgn))
+            .add_system(undo_redo)
+            .add_system(new_game)
+            .add_system(view_history)
+            .add_system_to_stage(CoreStage::PostUpdate, export_pgn.after(make_moves));
     }
 }
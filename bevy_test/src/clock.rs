@@ -0,0 +1,54 @@
+use crate::board::{make_moves, ChessGame, MoveMadeEvent, NewGameEvent};
+use crate::replay::Replay;
+use bevy::prelude::*;
+use chess_core::{Clock, GameResult};
+
+/// The players' clocks, `None` for games without a time limit.
+#[derive(Default, Resource)]
+pub struct ChessClock(pub Option<Clock>);
+
+/// Runs the clock of the side to move on `Time`, adds the bonus for every move made and ends the
+/// game once a flag falls.
+fn run_clock(
+    time: Res<Time>,
+    replay: Res<Replay>,
+    mut chess_clock: ResMut<ChessClock>,
+    mut game: ResMut<ChessGame>,
+    mut move_made_events: EventReader<MoveMadeEvent>,
+    mut new_game_events: EventReader<NewGameEvent>,
+    mut game_result_event: EventWriter<GameResult>,
+) {
+    let Some(clock) = chess_clock.0.as_mut() else {
+        return;
+    };
+    if new_game_events.iter().count() > 0 {
+        *clock = Clock::new(clock.control());
+    }
+    for _event in move_made_events.iter() {
+        clock.press();
+    }
+    if game.result().is_some() || replay.is_active() {
+        clock.stop();
+        return;
+    }
+
+    // the first move, or the turn changed through undo or redo
+    if clock.running() != Some(game.side_to_move()) {
+        clock.start(game.side_to_move());
+    }
+    if let Some(color) = clock.tick(time.delta()) {
+        if let Some(result) = game.flag(color) {
+            debug!("game over: {:?}", result);
+            game_result_event.send(result);
+        }
+    }
+}
+
+pub struct ClockPlugin;
+
+impl Plugin for ClockPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ChessClock>()
+            .add_system_to_stage(CoreStage::PostUpdate, run_clock.after(make_moves));
+    }
+}
@@ -0,0 +1,200 @@
+use crate::board::{make_moves, ChessGame, MakeMoveEvent, NewGameEvent};
+use crate::replay::Replay;
+use bevy::prelude::*;
+use bevy::tasks::{AsyncComputeTaskPool, Task};
+use chess_core::eval::evaluate;
+use chess_core::uci::UciEngine;
+use chess_core::{GameResult, Move, PieceColor, Position, Search, SearchLimits};
+use futures_lite::future;
+use std::time::Duration;
+
+/// The side the built-in engine plays, if any, and how long it thinks about each move.
+#[derive(Resource)]
+pub struct EnginePlayer {
+    pub color: Option<PieceColor>,
+    pub think_time: Duration,
+}
+
+impl Default for EnginePlayer {
+    fn default() -> Self {
+        Self {
+            color: None,
+            think_time: Duration::from_secs(1),
+        }
+    }
+}
+
+impl EnginePlayer {
+    pub fn plays(&self, color: PieceColor) -> bool {
+        self.color == Some(color)
+    }
+}
+
+/// A UCI engine process playing instead of the built-in search, it is lent to the background
+/// task while it thinks.
+#[derive(Default, Resource)]
+pub struct ExternalEngine(pub Option<UciEngine>);
+
+type SearchTask = Task<(Option<UciEngine>, Result<Move, String>)>;
+
+/// The search running in the background together with the position it was started from.
+#[derive(Default, Resource)]
+pub struct EngineTask {
+    task: Option<(Position, SearchTask)>,
+    /// The game was taken back or started over, the move found is thrown away.
+    discarded: bool,
+    /// Why the last search came back without a move and the position it was in, the engine
+    /// doesn't try that position again.
+    error: Option<(Position, String)>,
+}
+
+impl EngineTask {
+    /// Why the engine has no move in `position`, if it failed there.
+    pub fn error(&self, position: &Position) -> Option<&str> {
+        match &self.error {
+            Some((failed, err)) if failed == position => Some(err),
+            _ => None,
+        }
+    }
+
+    /// Drops the move of a search still running, it is left to finish so an external engine
+    /// comes back. A position the engine failed in gets another try.
+    pub fn discard(&mut self) {
+        self.discarded = self.task.is_some();
+        self.error = None;
+    }
+}
+
+/// Starts a search on the `AsyncComputeTaskPool` once it is the engine's move, so the frame
+/// loop keeps going while it thinks.
+fn start_search(
+    engine: Res<EnginePlayer>,
+    game: Res<ChessGame>,
+    replay: Res<Replay>,
+    mut external: ResMut<ExternalEngine>,
+    mut engine_task: ResMut<EngineTask>,
+) {
+    if engine_task.task.is_some()
+        || engine_task.error(game.position()).is_some()
+        || replay.is_active()
+        || game.result().is_some()
+        || !engine.plays(game.side_to_move())
+    {
+        return;
+    }
+
+    let position = *game.position();
+    let think_time = engine.think_time;
+    let external = external.0.take();
+    let game = game.0.clone();
+    let task = AsyncComputeTaskPool::get().spawn(async move {
+        let no_move = || "the engine found no move".to_string();
+        if let Some(mut external) = external {
+            let best_move = external
+                .best_move(&game, think_time)
+                .map_err(|err| err.to_string())
+                .and_then(|best_move| best_move.ok_or_else(no_move));
+            return (Some(external), best_move);
+        }
+        let mut search = Search::new(SearchLimits::time(think_time));
+        search.set_history(game.previous_hashes());
+        let best_move = search
+            .run(&position, |info| {
+                debug!("engine depth {} score {}", info.depth, info.score);
+            })
+            .and_then(|info| info.best_move());
+        (None, best_move.ok_or_else(no_move))
+    });
+    engine_task.task = Some((position, task));
+}
+
+/// Plays the engine's move through `MakeMoveEvent` once its search is done.
+fn finish_search(
+    game: Res<ChessGame>,
+    mut external: ResMut<ExternalEngine>,
+    mut engine_task: ResMut<EngineTask>,
+    mut make_move_event: EventWriter<MakeMoveEvent>,
+) {
+    let engine_task = &mut *engine_task;
+    let Some((position, task)) = &mut engine_task.task else {
+        return;
+    };
+    let Some((engine, best_move)) = future::block_on(future::poll_once(task)) else {
+        return;
+    };
+    let position = *position;
+    if engine.is_some() {
+        external.0 = engine;
+    }
+    // the game may have been taken back while the engine was thinking
+    if !engine_task.discarded && position == *game.position() {
+        match best_move {
+            Ok(mv) => make_move_event.send(MakeMoveEvent(mv)),
+            Err(err) => engine_task.error = Some((position, err)),
+        }
+    }
+    engine_task.task = None;
+    engine_task.discarded = false;
+}
+
+/// Below this many centipawns the engine takes a draw it is offered.
+const ACCEPT_DRAW_BELOW: i32 = -150;
+
+/// Answers a draw offered to the engine by how its side stands right now.
+fn answer_draw_offer(
+    engine: Res<EnginePlayer>,
+    mut game: ResMut<ChessGame>,
+    mut game_result_event: EventWriter<GameResult>,
+) {
+    let Some(color) = engine.color else {
+        return;
+    };
+    if game.draw_offer() != Some(color.opposite()) {
+        return;
+    }
+    // the evaluation is from the side to move's point of view
+    let mut score = evaluate(game.position());
+    if game.side_to_move() != color {
+        score = -score;
+    }
+    if score < ACCEPT_DRAW_BELOW {
+        if let Some(result) = game.accept_draw() {
+            debug!("engine accepts the draw");
+            game_result_event.send(result);
+        }
+    } else {
+        debug!("engine declines the draw");
+        game.decline_draw();
+    }
+}
+
+/// Lets the external engine know the next position belongs to a different game.
+fn reset_external_engine(
+    mut new_game_events: EventReader<NewGameEvent>,
+    mut external: ResMut<ExternalEngine>,
+) {
+    if new_game_events.iter().count() == 0 {
+        return;
+    }
+    if let Some(engine) = &mut external.0 {
+        if let Err(err) = engine.new_game() {
+            warn!("{}", err);
+        }
+    }
+}
+
+pub struct EnginePlugin;
+
+impl Plugin for EnginePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EnginePlayer>()
+            .init_resource::<ExternalEngine>()
+            .init_resource::<EngineTask>()
+            // started once the move found last is on the board, so the same position isn't
+            // searched twice
+            .add_system_to_stage(CoreStage::PostUpdate, start_search.after(make_moves))
+            .add_system(reset_external_engine)
+            .add_system_to_stage(CoreStage::PostUpdate, answer_draw_offer)
+            .add_system(finish_search);
+    }
+}
@@ -1,13 +1,21 @@
 mod board;
+mod clock;
+mod engine;
 mod example;
 mod pieces;
+mod replay;
 mod ui;
 
+use crate::clock::{ChessClock, ClockPlugin};
+use crate::engine::{EnginePlayer, EnginePlugin, ExternalEngine};
 use crate::pieces::PiecesPlugin;
+use crate::replay::{Replay, ReplayPlugin};
 use crate::ui::ChessUIPlugin;
 use bevy::prelude::*;
 use bevy_mod_picking::*;
-use board::BoardPlugin;
+use board::{BoardPlugin, ChessGame, PlayerTurn};
+use chess_core::uci::UciEngine;
+use chess_core::{Clock, Game, PieceColor, Position, TimeControl};
 
 fn setup(mut commands: Commands) {
     commands.spawn((
@@ -26,8 +34,106 @@ fn setup(mut commands: Commands) {
     });
 }
 
+/// The value following `flag` on the command line.
+fn arg_value(flag: &str) -> Option<String> {
+    let mut args = std::env::args().skip(1);
+    args.find(|arg| arg == flag)?;
+    args.next()
+}
+
+/// Game to start from, `--fen "<fen>"` sets up that position instead of the usual one.
+fn starting_game() -> Game {
+    let Some(fen) = arg_value("--fen") else {
+        return Game::default();
+    };
+    match Position::from_fen(&fen) {
+        Ok(position) => Game::new(position),
+        Err(err) => {
+            println!("{}, starting from the initial position", err);
+            Game::default()
+        }
+    }
+}
+
+/// `--pgn <file>` replays the first game of the file, `--game <n>` picks another one.
+fn starting_replay() -> Replay {
+    let Some(path) = arg_value("--pgn") else {
+        return Replay::default();
+    };
+    let index = arg_value("--game")
+        .and_then(|n| n.parse::<usize>().ok())
+        .map_or(0, |n| n.saturating_sub(1));
+    Replay::load(&path, index).unwrap_or_else(|err| {
+        println!("{}", err);
+        Replay::default()
+    })
+}
+
+/// `--engine white` or `--engine black` has the built-in engine play that side, `--think <ms>`
+/// sets how long it spends on a move.
+fn engine_player() -> EnginePlayer {
+    let mut engine = EnginePlayer::default();
+    engine.color = match arg_value("--engine").as_deref() {
+        Some("white") => Some(PieceColor::White),
+        Some("black") => Some(PieceColor::Black),
+        Some(other) => {
+            println!("--engine takes white or black, not {}", other);
+            None
+        }
+        None => None,
+    };
+    if let Some(ms) = arg_value("--think").and_then(|ms| ms.parse().ok()) {
+        engine.think_time = std::time::Duration::from_millis(ms);
+    }
+    engine
+}
+
+/// `--uci <path>` has a UCI engine executable play the engine's side instead of the built-in
+/// search.
+fn external_engine() -> ExternalEngine {
+    let Some(path) = arg_value("--uci") else {
+        return ExternalEngine::default();
+    };
+    match UciEngine::spawn(path.as_str(), &[]) {
+        Ok(engine) => {
+            println!(
+                "playing against {}",
+                engine.name.as_deref().unwrap_or(&path)
+            );
+            ExternalEngine(Some(engine))
+        }
+        Err(err) => {
+            println!("couldn't start {}: {}", path, err);
+            ExternalEngine::default()
+        }
+    }
+}
+
+/// `--clock <minutes>[+|b|d<seconds>]` plays with clocks, the seconds being a Fischer increment,
+/// a Bronstein delay or a simple delay.
+fn chess_clock() -> ChessClock {
+    let Some(control) = arg_value("--clock") else {
+        return ChessClock::default();
+    };
+    match control.parse::<TimeControl>() {
+        Ok(control) => ChessClock(Some(Clock::new(control))),
+        Err(err) => {
+            println!("{}, playing without clocks", err);
+            ChessClock::default()
+        }
+    }
+}
+
 fn main() {
+    let replay = starting_replay();
+    let game = replay.game().unwrap_or_else(starting_game);
     App::new()
+        .insert_resource(replay)
+        .insert_resource(engine_player())
+        .insert_resource(external_engine())
+        .insert_resource(chess_clock())
+        .insert_resource(PlayerTurn(game.side_to_move()))
+        .insert_resource(ChessGame(game))
         .insert_resource(Msaa {
             samples: 4,
             ..default()
@@ -45,6 +151,9 @@ fn main() {
         .add_plugin(BoardPlugin)
         .add_plugin(PiecesPlugin)
         .add_plugin(ChessUIPlugin)
+        .add_plugin(ReplayPlugin)
+        .add_plugin(EnginePlugin)
+        .add_plugin(ClockPlugin)
         .add_startup_system(setup)
         .run();
 }
@@ -1,37 +1,14 @@
+use crate::board::{make_moves, new_game, ChessGame, HistoryView, MoveMadeEvent, NewGameEvent};
 use bevy::asset::{AssetServer, Assets, Handle};
-use bevy::hierarchy::BuildChildren;
+use bevy::hierarchy::{BuildChildren, ChildBuilder};
 use bevy::math::Vec3;
 use bevy::pbr::{PbrBundle, StandardMaterial};
-use bevy::prelude::{Color, Commands, Component, Mesh, Res, ResMut, Transform};
-use std::cell::RefCell;
-use std::collections::{HashMap, HashSet};
-use std::rc::Rc;
+use bevy::prelude::{Color, Commands, Component, Mesh, Res, ResMut, Resource, Transform};
+use chess_core::{Position, Square};
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum PieceType {
-    Pawn,
-    Rook,
-    Knight,
-    Bishop,
-    Queen,
-    King,
-}
-
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum PieceColor {
-    White,
-    Black,
-}
-
-fn color_of_square(pos: (u8, u8), pieces: &Vec<Piece>) -> Option<PieceColor> {
-    for piece in pieces {
-        if piece.x == pos.0 && piece.y == pos.1 {
-            return Some(piece.color);
-        }
-    }
-    None
-}
+pub use chess_core::{PieceColor, PieceType};
 
+/// Mirror of a piece on the `ChessGame` board, `x` is the rank and `y` the file.
 #[derive(Debug, Clone, Copy, Component)]
 pub struct Piece {
     pub piece_type: PieceType,
@@ -40,574 +17,156 @@ pub struct Piece {
     pub y: u8,
 }
 
-const KNIGHT_MOVES: [(i8, i8); 8] = [
-    (-2, -1),
-    (-2, 1),
-    (-1, -2),
-    (-1, 2),
-    (1, -2),
-    (1, 2),
-    (2, -1),
-    (2, 1),
-];
-
-const KING_MOVES: [(i8, i8); 8] = [
-    (-1, -1),
-    (-1, 0),
-    (-1, 1),
-    (0, -1),
-    (0, 1),
-    (1, -1),
-    (1, 0),
-    (1, 1),
-];
-
 impl Piece {
-    pub fn possible_moves(&self, pieces: HashMap<(u8, u8), &Piece>) -> HashSet<(u8, u8)> {
-        // todo add takes
-        let mut moves = HashSet::new();
-        match &self.piece_type {
-            PieceType::Pawn => {
-                if self.color == PieceColor::Black {
-                    if self.x >= 0 {
-                        let x1 = self.x as i8 - 1;
-                        let x1 = x1 as u8;
-                        if let Some(p) = pieces.get(&(x1, self.y)) {
-                            if p.color != self.color {
-                                moves.insert((x1, self.y));
-                            }
-                        } else {
-                            moves.insert((x1, self.y));
-                        }
-                    }
-                    if self.x == 6 {
-                        // todo implement en passant
-                        let x2 = self.x - 2;
-                        if let Some(p) = pieces.get(&(x2, self.y)) {
-                            if p.color != self.color {
-                                moves.insert((x2, self.y));
-                            }
-                        } else {
-                            moves.insert((x2, self.y));
-                        }
-                    }
-                } else {
-                    if self.x <= 7 {
-                        let x1 = self.x + 1;
-                        if let Some(p) = pieces.get(&(x1, self.y)) {
-                            if p.color != self.color {
-                                moves.insert((x1, self.y));
-                            }
-                        } else {
-                            moves.insert((x1, self.y));
-                        }
-                    }
-                    if self.x == 1 {
-                        // todo implement en passant
-                        let x2 = self.x + 2;
-                        if let Some(p) = pieces.get(&(x2, self.y)) {
-                            if p.color != self.color {
-                                moves.insert((x2, self.y));
-                            }
-                        } else {
-                            moves.insert((x2, self.y));
-                        }
-                    }
-                }
-            }
-            PieceType::Rook => {
-                for i in self.x + 1..8 {
-                    if pieces.get(&(i, self.y)).is_some() {
-                        break;
-                    }
-                    moves.insert((i, self.y));
-                }
-                for i in 0..self.x {
-                    if pieces.get(&(i, self.y)).is_some() {
-                        break;
-                    }
-                    moves.insert((i, self.y));
-                }
-                for i in self.y + 1..8 {
-                    if pieces.get(&(self.x, i)).is_some() {
-                        break;
-                    }
-                    moves.insert((self.x, i));
-                }
-                for i in 0..self.y {
-                    if pieces.get(&(self.x, i)).is_some() {
-                        break;
-                    }
-                    moves.insert((self.x, i));
-                }
-            }
-            PieceType::Knight => {
-                for i in KNIGHT_MOVES {
-                    let pos = (self.x as i8 + i.0, self.y as i8 + i.1);
-                    if pos.0 >= 0 && pos.0 < 8 && pos.1 >= 0 && pos.1 < 8 {
-                        if pieces.get(&(pos.0 as u8, pos.1 as u8)).is_none() {
-                            moves.insert((pos.0 as u8, pos.1 as u8));
-                        }
-                    }
-                }
-            }
-            PieceType::Bishop => {
-                let sx = self.x as i8;
-                let sy = self.y as i8;
-
-                // todo shouldn't need to break
-                // // top, bottom, left, right
-                // let xd = (sx as f64 - 3.5).abs() as u8;
-                // let yd = (sy as f64 - 3.5).abs() as u8;
-                // let min = sx.min(sy);
-                // let max = sx.max(sy);
-
-                for i in 1..8 {
-                    let x = sx + i;
-                    let y = sy + i;
-                    if x > 7 || y > 7 {
-                        break;
-                    } else if let Some(p) = pieces.get(&(x as u8, y as u8)) {
-                        if p.color != self.color {
-                            moves.insert((x as u8, y as u8));
-                        }
-                        break;
-                    }
-                    moves.insert((x as u8, y as u8));
-                }
-
-                for i in 1..8 {
-                    let x = sx - i;
-                    let y = sy + i;
-                    if x < 0 || y > 7 {
-                        break;
-                    } else if let Some(p) = pieces.get(&(x as u8, y as u8)) {
-                        if p.color != self.color {
-                            moves.insert((x as u8, y as u8));
-                        }
-                        break;
-                    }
-                    moves.insert((x as u8, y as u8));
-                }
+    pub fn from_core(square: Square, piece: chess_core::Piece) -> Self {
+        Self {
+            piece_type: piece.piece_type,
+            color: piece.color,
+            x: square.rank(),
+            y: square.file(),
+        }
+    }
 
-                for i in 1..8 {
-                    let x = sx + i;
-                    let y = sy - i;
-                    if x > 7 || y < 0 {
-                        break;
-                    } else if let Some(p) = pieces.get(&(x as u8, y as u8)) {
-                        if p.color != self.color {
-                            moves.insert((x as u8, y as u8));
-                        }
-                        break;
-                    }
-                    moves.insert((x as u8, y as u8));
-                }
+    pub fn to_core(&self) -> chess_core::Piece {
+        chess_core::Piece::new(self.piece_type, self.color)
+    }
 
-                for i in 1..8 {
-                    let x = sx - i;
-                    let y = sy - i;
-                    if x < 0 || y < 0 {
-                        break;
-                    } else if let Some(p) = pieces.get(&(x as u8, y as u8)) {
-                        if p.color != self.color {
-                            moves.insert((x as u8, y as u8));
-                        }
-                        break;
-                    }
-                    moves.insert((x as u8, y as u8));
-                }
-                println!("{:?}", moves);
-            }
-            PieceType::Queen => {
-                for i in self.x + 1..8 {
-                    if pieces.get(&(i, self.y)).is_some() {
-                        break;
-                    }
-                    moves.insert((i, self.y));
-                }
-                for i in (0..self.x).rev() {
-                    if pieces.get(&(i, self.y)).is_some() {
-                        break;
-                    }
-                    moves.insert((i, self.y));
-                }
-                for i in self.y + 1..8 {
-                    if pieces.get(&(self.x, i)).is_some() {
-                        break;
-                    }
-                    moves.insert((self.x, i));
-                }
-                for i in (0..self.y).rev() {
-                    if pieces.get(&(self.x, i)).is_some() {
-                        break;
-                    }
-                    moves.insert((self.x, i));
-                }
+    pub fn square(&self) -> Square {
+        Square::new(self.x, self.y)
+    }
 
-                let sx = self.x as i8;
-                let sy = self.y as i8;
+    fn move_to(&mut self, square: Square) {
+        self.x = square.rank();
+        self.y = square.file();
+    }
+}
 
-                for i in 1..8 {
-                    let x = sx + i;
-                    let y = sy + i;
-                    if x > 7 || y > 7 {
-                        break;
-                    } else if let Some(p) = pieces.get(&(x as u8, y as u8)) {
-                        if p.color != self.color {
-                            moves.insert((x as u8, y as u8));
-                        }
-                        break;
-                    }
-                    moves.insert((x as u8, y as u8));
-                }
+/// Reads the placement of the piece entities back into a position, which only covers the pieces
+/// so the side to move, castling and en passant are left at their `Position::empty` values.
+pub fn position_from_pieces<'a>(pieces: impl IntoIterator<Item = &'a Piece>) -> Position {
+    let mut position = Position::empty();
+    for piece in pieces {
+        position.put(piece.square(), piece.to_core());
+    }
+    position
+}
 
-                for i in 1..8 {
-                    let x = sx - i;
-                    let y = sy + i;
-                    if x < 0 || y > 7 {
-                        break;
-                    } else if let Some(p) = pieces.get(&(x as u8, y as u8)) {
-                        if p.color != self.color {
-                            moves.insert((x as u8, y as u8));
-                        }
-                        break;
-                    }
-                    moves.insert((x as u8, y as u8));
-                }
+/// Mesh and material handles loaded in `create_pieces`, kept around to re-dress pieces later.
+#[derive(Resource, Clone)]
+pub struct PieceMeshes {
+    king: Handle<Mesh>,
+    king_cross: Handle<Mesh>,
+    pawn: Handle<Mesh>,
+    knight_1: Handle<Mesh>,
+    knight_2: Handle<Mesh>,
+    rook: Handle<Mesh>,
+    bishop: Handle<Mesh>,
+    queen: Handle<Mesh>,
+    white_material: Handle<StandardMaterial>,
+    black_material: Handle<StandardMaterial>,
+}
 
-                for i in 1..8 {
-                    let x = sx + i;
-                    let y = sy - i;
-                    if x > 7 || y < 0 {
-                        break;
-                    } else if let Some(p) = pieces.get(&(x as u8, y as u8)) {
-                        if p.color != self.color {
-                            moves.insert((x as u8, y as u8));
-                        }
-                        break;
-                    }
-                    moves.insert((x as u8, y as u8));
-                }
+impl PieceMeshes {
+    pub fn material(&self, color: PieceColor) -> Handle<StandardMaterial> {
+        match color {
+            PieceColor::White => self.white_material.clone(),
+            PieceColor::Black => self.black_material.clone(),
+        }
+    }
 
-                for i in 1..8 {
-                    let x = sx - i;
-                    let y = sy - i;
-                    if x < 0 || y < 0 {
-                        break;
-                    } else if let Some(p) = pieces.get(&(x as u8, y as u8)) {
-                        if p.color != self.color {
-                            moves.insert((x as u8, y as u8));
-                        }
-                        break;
-                    }
-                    moves.insert((x as u8, y as u8));
-                }
-            }
-            PieceType::King => {
-                for i in KING_MOVES.iter() {
-                    let pos = (self.x as i8 + i.0, self.y as i8 + i.1);
-                    if pos.0 >= 0 && pos.0 < 8 && pos.1 >= 0 && pos.1 < 8 {
-                        if pieces.get(&(pos.0 as u8, pos.1 as u8)).is_none() {
-                            moves.insert((pos.0 as u8, pos.1 as u8));
-                        }
-                    }
-                }
-            }
-        };
-        moves
+    /// The meshes making up `piece_type`, each with its offset from the piece's square.
+    fn meshes(&self, piece_type: PieceType) -> Vec<(Handle<Mesh>, Vec3)> {
+        match piece_type {
+            PieceType::Pawn => vec![(self.pawn.clone(), Vec3::new(-0.2, 0., 2.6))],
+            PieceType::Rook => vec![(self.rook.clone(), Vec3::new(-0.1, 0., 1.8))],
+            PieceType::Knight => vec![
+                (self.knight_1.clone(), Vec3::new(-0.2, 0., 0.9)),
+                (self.knight_2.clone(), Vec3::new(-0.2, 0., 0.9)),
+            ],
+            PieceType::Bishop => vec![(self.bishop.clone(), Vec3::new(-0.1, 0., 0.))],
+            PieceType::Queen => vec![(self.queen.clone(), Vec3::new(-0.2, 0., -0.95))],
+            PieceType::King => vec![
+                (self.king.clone(), Vec3::new(-0.2, 0., -1.9)),
+                (self.king_cross.clone(), Vec3::new(-0.2, 0., -1.9)),
+            ],
+        }
     }
 }
 
-pub fn spawn_two(
-    commands: Rc<RefCell<Commands>>,
-    material: Handle<StandardMaterial>,
+/// Spawns the child meshes of a piece, also used when a piece changes type after a promotion.
+pub fn spawn_piece_meshes(
+    parent: &mut ChildBuilder,
+    meshes: &PieceMeshes,
     piece_type: PieceType,
-    piece_color: PieceColor,
-    mesh: Handle<Mesh>,
-    mesh_cross: Handle<Mesh>,
-    position: Vec3,
-    transform1: Vec3,
-    transform2: Vec3,
+    color: PieceColor,
 ) {
-    commands
-        .borrow_mut()
-        .spawn(PbrBundle {
-            transform: Transform::from_translation(position),
+    let material = meshes.material(color);
+    for (mesh, offset) in meshes.meshes(piece_type) {
+        parent.spawn(PbrBundle {
+            mesh,
+            material: material.clone(),
+            transform: {
+                let mut transform = Transform::from_translation(offset);
+                transform.scale *= Vec3::new(0.2, 0.2, 0.2);
+                transform
+            },
             ..Default::default()
-        })
-        .insert(Piece {
-            piece_type,
-            color: piece_color,
-            x: position[0] as u8,
-            y: position[2] as u8,
-        })
-        .with_children(|parent| {
-            parent.spawn(PbrBundle {
-                mesh,
-                material: material.clone(),
-                transform: {
-                    let mut transform = Transform::from_translation(transform1);
-                    transform.scale *= Vec3::new(0.2, 0.2, 0.2);
-                    transform
-                },
-                ..Default::default()
-            });
-            parent.spawn(PbrBundle {
-                mesh: mesh_cross,
-                material,
-                transform: {
-                    let mut transform = Transform::from_translation(transform2);
-                    transform.scale *= Vec3::new(0.2, 0.2, 0.2);
-                    transform
-                },
-                ..Default::default()
-            });
         });
+    }
 }
 
-fn spawn_one(
-    commands: Rc<RefCell<Commands>>,
-    material: Handle<StandardMaterial>,
-    piece_type: PieceType,
-    piece_color: PieceColor,
-    mesh: Handle<Mesh>,
-    position: Vec3,
-    transform: Vec3,
+/// Spawns a piece entity with its meshes standing on `square`.
+pub fn spawn_piece(
+    commands: &mut Commands,
+    meshes: &PieceMeshes,
+    piece: chess_core::Piece,
+    square: Square,
 ) {
     commands
-        .borrow_mut()
         .spawn(PbrBundle {
-            transform: Transform::from_translation(position),
+            transform: Transform::from_translation(Vec3::new(
+                square.rank() as f32,
+                0.,
+                square.file() as f32,
+            )),
             ..Default::default()
         })
-        .insert(Piece {
-            piece_type,
-            color: piece_color,
-            x: position[0] as u8,
-            y: position[2] as u8,
-        })
+        .insert(Piece::from_core(square, piece))
         .with_children(|parent| {
-            parent.spawn(PbrBundle {
-                mesh,
-                material,
-                transform: {
-                    let mut transform = Transform::from_translation(transform);
-                    transform.scale *= Vec3::new(0.2, 0.2, 0.2);
-                    transform
-                },
-                ..Default::default()
-            });
+            spawn_piece_meshes(parent, meshes, piece.piece_type, piece.color);
         });
 }
 
+/// Spawns an entity for every piece of `position`.
+fn spawn_pieces(commands: &mut Commands, meshes: &PieceMeshes, position: &Position) {
+    for (square, piece) in position.pieces() {
+        spawn_piece(commands, meshes, piece, square);
+    }
+}
+
 pub fn create_pieces(
-    commands: Commands,
+    mut commands: Commands,
     asset_server: Res<AssetServer>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    game: Res<ChessGame>,
 ) {
-    let king_handle: Handle<Mesh> =
-        asset_server.load("models/chess_kit/pieces.glb#Mesh0/Primitive0");
-    let king_cross_handle: Handle<Mesh> =
-        asset_server.load("models/chess_kit/pieces.glb#Mesh1/Primitive0");
-    let pawn_handle: Handle<Mesh> =
-        asset_server.load("models/chess_kit/pieces.glb#Mesh2/Primitive0");
-    let knight_1_handle: Handle<Mesh> =
-        asset_server.load("models/chess_kit/pieces.glb#Mesh3/Primitive0");
-    let knight_2_handle: Handle<Mesh> =
-        asset_server.load("models/chess_kit/pieces.glb#Mesh4/Primitive0");
-    let rook_handle: Handle<Mesh> =
-        asset_server.load("models/chess_kit/pieces.glb#Mesh5/Primitive0");
-    let bishop_handle: Handle<Mesh> =
-        asset_server.load("models/chess_kit/pieces.glb#Mesh6/Primitive0");
-    let queen_handle: Handle<Mesh> =
-        asset_server.load("models/chess_kit/pieces.glb#Mesh7/Primitive0");
+    let meshes = PieceMeshes {
+        king: asset_server.load("models/chess_kit/pieces.glb#Mesh0/Primitive0"),
+        king_cross: asset_server.load("models/chess_kit/pieces.glb#Mesh1/Primitive0"),
+        pawn: asset_server.load("models/chess_kit/pieces.glb#Mesh2/Primitive0"),
+        knight_1: asset_server.load("models/chess_kit/pieces.glb#Mesh3/Primitive0"),
+        knight_2: asset_server.load("models/chess_kit/pieces.glb#Mesh4/Primitive0"),
+        rook: asset_server.load("models/chess_kit/pieces.glb#Mesh5/Primitive0"),
+        bishop: asset_server.load("models/chess_kit/pieces.glb#Mesh6/Primitive0"),
+        queen: asset_server.load("models/chess_kit/pieces.glb#Mesh7/Primitive0"),
+        white_material: materials.add(Color::rgb(1.0, 0.8, 0.8).into()),
+        black_material: materials.add(Color::rgb(0., 0.2, 0.2).into()),
+    };
 
-    let white_material = materials.add(Color::rgb(1.0, 0.8, 0.8).into());
-    let black_material = materials.add(Color::rgb(0., 0.2, 0.2).into());
-    let commands = Rc::new(RefCell::new(commands));
-    // rook
-    spawn_one(
-        commands.clone(),
-        white_material.clone(),
-        PieceType::Rook,
-        PieceColor::White,
-        rook_handle.clone(),
-        Vec3::new(0.0, 0.0, 0.0),
-        Vec3::new(-0.1, 0., 1.8),
-    );
-    spawn_two(
-        commands.clone(),
-        white_material.clone(),
-        PieceType::Knight,
-        PieceColor::White,
-        knight_1_handle.clone(),
-        knight_2_handle.clone(),
-        Vec3::new(0., 0., 1.),
-        Vec3::new(-0.2, 0., 0.9),
-        Vec3::new(-0.2, 0., 0.9),
-    );
-    spawn_one(
-        commands.clone(),
-        white_material.clone(),
-        PieceType::Bishop,
-        PieceColor::White,
-        bishop_handle.clone(),
-        Vec3::new(0.0, 0.0, 2.),
-        Vec3::new(-0.1, 0., 0.),
-    );
-    spawn_one(
-        commands.clone(),
-        white_material.clone(),
-        PieceType::Queen,
-        PieceColor::White,
-        queen_handle.clone(),
-        Vec3::new(0.0, 0.0, 3.),
-        Vec3::new(-0.2, 0., -0.95),
-    );
-    spawn_two(
-        commands.clone(),
-        white_material.clone(),
-        PieceType::King,
-        PieceColor::White,
-        king_handle.clone(),
-        king_cross_handle.clone(),
-        Vec3::new(0., 0., 4.),
-        Vec3::new(-0.2, 0., -1.9),
-        Vec3::new(-0.2, 0., -1.9),
-    );
-    spawn_one(
-        commands.clone(),
-        white_material.clone(),
-        PieceType::Bishop,
-        PieceColor::White,
-        bishop_handle.clone(),
-        Vec3::new(0.0, 0.0, 5.),
-        Vec3::new(-0.1, 0., 0.),
-    );
-    spawn_two(
-        commands.clone(),
-        white_material.clone(),
-        PieceType::Knight,
-        PieceColor::White,
-        knight_1_handle.clone(),
-        knight_2_handle.clone(),
-        Vec3::new(0., 0., 6.),
-        Vec3::new(-0.2, 0., 0.9),
-        Vec3::new(-0.2, 0., 0.9),
-    );
-    spawn_one(
-        commands.clone(),
-        white_material.clone(),
-        PieceType::Rook,
-        PieceColor::White,
-        rook_handle.clone(),
-        Vec3::new(0.0, 0.0, 7.),
-        Vec3::new(-0.1, 0., 1.8),
-    );
-
-    for i in 0..8 {
-        spawn_one(
-            commands.clone(),
-            white_material.clone(),
-            PieceType::Pawn,
-            PieceColor::White,
-            pawn_handle.clone(),
-            Vec3::new(1.0, 0., i as f32),
-            Vec3::new(-0.2, 0., 2.6),
-        );
-    }
-    spawn_one(
-        commands.clone(),
-        black_material.clone(),
-        PieceType::Rook,
-        PieceColor::Black,
-        rook_handle.clone(),
-        Vec3::new(7., 0.0, 0.0),
-        Vec3::new(-0.1, 0., 1.8),
-    );
-    spawn_two(
-        commands.clone(),
-        black_material.clone(),
-        PieceType::Knight,
-        PieceColor::Black,
-        knight_1_handle.clone(),
-        knight_2_handle.clone(),
-        Vec3::new(7., 0., 1.),
-        Vec3::new(-0.2, 0., 0.9),
-        Vec3::new(-0.2, 0., 0.9),
-    );
-    spawn_one(
-        commands.clone(),
-        black_material.clone(),
-        PieceType::Bishop,
-        PieceColor::Black,
-        bishop_handle.clone(),
-        Vec3::new(7., 0.0, 2.),
-        Vec3::new(-0.1, 0., 0.),
-    );
-    spawn_one(
-        commands.clone(),
-        black_material.clone(),
-        PieceType::Queen,
-        PieceColor::Black,
-        queen_handle.clone(),
-        Vec3::new(7., 0.0, 3.),
-        Vec3::new(-0.2, 0., -0.95),
-    );
-    spawn_two(
-        commands.clone(),
-        black_material.clone(),
-        PieceType::King,
-        PieceColor::Black,
-        king_handle.clone(),
-        king_cross_handle.clone(),
-        Vec3::new(7., 0., 4.),
-        Vec3::new(-0.2, 0., -1.9),
-        Vec3::new(-0.2, 0., -1.9),
-    );
-    spawn_one(
-        commands.clone(),
-        black_material.clone(),
-        PieceType::Bishop,
-        PieceColor::Black,
-        bishop_handle.clone(),
-        Vec3::new(7., 0.0, 5.),
-        Vec3::new(-0.1, 0., 0.),
-    );
-    spawn_two(
-        commands.clone(),
-        black_material.clone(),
-        PieceType::Knight,
-        PieceColor::Black,
-        knight_1_handle.clone(),
-        knight_2_handle.clone(),
-        Vec3::new(7., 0., 6.),
-        Vec3::new(-0.2, 0., 0.9),
-        Vec3::new(-0.2, 0., 0.9),
-    );
-    spawn_one(
-        commands.clone(),
-        black_material.clone(),
-        PieceType::Rook,
-        PieceColor::Black,
-        rook_handle.clone(),
-        Vec3::new(7., 0.0, 7.),
-        Vec3::new(-0.1, 0., 1.8),
-    );
-
-    for i in 0..8 {
-        spawn_one(
-            commands.clone(),
-            black_material.clone(),
-            PieceType::Pawn,
-            PieceColor::Black,
-            pawn_handle.clone(),
-            Vec3::new(6., 0., i as f32),
-            Vec3::new(-0.2, 0., 2.6),
-        );
-    }
+    spawn_pieces(&mut commands, &meshes, game.position());
+    commands.insert_resource(meshes);
 }
+
 use bevy::prelude::*;
 
 fn move_pieces(time: Res<Time>, mut query: Query<(&mut Transform, &Piece)>) {
@@ -620,11 +179,171 @@ fn move_pieces(time: Res<Time>, mut query: Query<(&mut Transform, &Piece)>) {
     }
 }
 
+/// Replays the moves made on the `ChessGame` onto the piece entities.
+fn mirror_moves(
+    mut commands: Commands,
+    meshes: Res<PieceMeshes>,
+    view: Res<HistoryView>,
+    mut move_made_events: EventReader<MoveMadeEvent>,
+    mut pieces_query: Query<(Entity, &mut Piece), Without<Taken>>,
+) {
+    if view.is_active() {
+        // the pieces are synced to the game once the view goes back to it
+        move_made_events.clear();
+        return;
+    }
+    for event in move_made_events.iter() {
+        let mv = event.0;
+        let castling_rook = mv.castling_rook();
+        for (entity, mut piece) in pieces_query.iter_mut() {
+            let square = piece.square();
+            if Some(square) == mv.captured_square() {
+                trace!("taking piece {:?}", piece);
+                // Mark the piece as taken
+                commands.entity(entity).insert(Taken);
+            } else if square == mv.from {
+                piece.move_to(mv.to);
+                if let Some(piece_type) = mv.promotion {
+                    // Swap the pawn's meshes for the new piece's
+                    piece.piece_type = piece_type;
+                    let mut piece_commands = commands.entity(entity);
+                    piece_commands.despawn_descendants();
+                    piece_commands.with_children(|parent| {
+                        spawn_piece_meshes(parent, &meshes, piece_type, piece.color);
+                    });
+                }
+                trace!("moving piece {:?}", piece);
+            } else if let Some((rook_from, rook_to)) = castling_rook {
+                // Castling, bring the rook over to the other side of the king
+                if square == rook_from {
+                    piece.move_to(rook_to);
+                }
+            }
+        }
+    }
+}
+
+/// Asks for the piece entities to be rearranged to match the `ChessGame` position, for jumps
+/// that aren't a single move such as stepping back through a game.
+pub struct SyncPiecesEvent;
+
+/// Rearranges the pieces to the position `HistoryView` shows. Keeps entities already on the
+/// right square, slides the others over to squares missing their kind of piece and spawns or
+/// takes whatever is left.
+fn sync_pieces(
+    mut commands: Commands,
+    meshes: Res<PieceMeshes>,
+    game: Res<ChessGame>,
+    view: Res<HistoryView>,
+    mut sync_events: EventReader<SyncPiecesEvent>,
+    mut pieces_query: Query<(Entity, &mut Piece), Without<Taken>>,
+) {
+    if sync_events.iter().count() == 0 {
+        return;
+    }
+    let position = view.position(&game);
+    // nothing to do when the entities already show the position
+    let shown = position_from_pieces(pieces_query.iter().map(|(_, piece)| piece));
+    if shown.pieces().eq(position.pieces()) {
+        return;
+    }
+
+    let mut missing = position
+        .pieces()
+        .collect::<Vec<(Square, chess_core::Piece)>>();
+    let mut misplaced = Vec::new();
+    for (entity, piece) in pieces_query.iter() {
+        match missing
+            .iter()
+            .position(|&(square, core)| square == piece.square() && core == piece.to_core())
+        {
+            Some(index) => {
+                missing.swap_remove(index);
+            }
+            None => misplaced.push(entity),
+        }
+    }
+
+    for (square, core) in missing {
+        let matching = misplaced.iter().position(|&entity| {
+            pieces_query
+                .get(entity)
+                .map_or(false, |(_, piece)| piece.to_core() == core)
+        });
+        match matching {
+            Some(index) => {
+                let entity = misplaced.swap_remove(index);
+                if let Ok((_, mut piece)) = pieces_query.get_mut(entity) {
+                    piece.move_to(square);
+                }
+            }
+            None => spawn_piece(&mut commands, &meshes, core, square),
+        }
+    }
+    for entity in misplaced {
+        commands.entity(entity).insert(Taken);
+    }
+}
+
+/// Clears the board and sets it up again the way `create_pieces` did, for a new game.
+fn reset_pieces(
+    mut commands: Commands,
+    meshes: Res<PieceMeshes>,
+    game: Res<ChessGame>,
+    mut new_game_events: EventReader<NewGameEvent>,
+    pieces_query: Query<Entity, With<Piece>>,
+) {
+    if new_game_events.iter().count() == 0 {
+        return;
+    }
+    for entity in pieces_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    spawn_pieces(&mut commands, &meshes, game.position());
+}
+
+#[derive(Component)]
+struct Taken;
+
+fn despawn_taken_pieces(mut commands: Commands, query: Query<(Entity, &Piece, &Taken)>) {
+    for (entity, _piece, _taken) in query.iter() {
+        // Despawn piece and children
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
 pub struct PiecesPlugin;
 
 impl Plugin for PiecesPlugin {
     fn build(&self, app: &mut App) {
         app.add_startup_system(create_pieces)
-            .add_system(move_pieces);
+            .add_system(move_pieces)
+            .add_system(reset_pieces.after(new_game))
+            .add_event::<SyncPiecesEvent>()
+            .add_system_to_stage(CoreStage::PostUpdate, mirror_moves.after(make_moves))
+            .add_system_to_stage(CoreStage::PostUpdate, sync_pieces.after(mirror_moves))
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                despawn_taken_pieces.after(sync_pieces),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pieces_read_back_into_a_position() {
+        let position = Position::from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        )
+        .unwrap();
+        let pieces = position
+            .pieces()
+            .map(|(square, piece)| Piece::from_core(square, piece))
+            .collect::<Vec<Piece>>();
+        assert!(position_from_pieces(&pieces).pieces().eq(position.pieces()));
+        assert_eq!(position_from_pieces(&[]), Position::empty());
     }
 }
@@ -0,0 +1,87 @@
+use crate::board::{ChessGame, PlayerTurn};
+use crate::pieces::SyncPiecesEvent;
+use bevy::prelude::*;
+use chess_core::{parse_pgn, Game, PgnGame};
+
+/// A game loaded from PGN being stepped through, the board is read-only while it is set.
+#[derive(Default, Resource)]
+pub struct Replay {
+    game: Option<PgnGame>,
+    /// Moves of `game` shown on the board.
+    ply: usize,
+}
+
+impl Replay {
+    /// Reads game `index` (counted from 0) of the PGN file at `path`.
+    pub fn load(path: &str, index: usize) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+        let mut games = parse_pgn(&text).map_err(|err| format!("{}: {}", path, err))?;
+        if index >= games.len() {
+            return Err(format!("{} holds {} games", path, games.len()));
+        }
+        Ok(Self {
+            game: Some(games.swap_remove(index)),
+            ply: 0,
+        })
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.game.is_some()
+    }
+
+    /// The game as far as the replay got.
+    pub fn game(&self) -> Option<Game> {
+        self.game.as_ref().map(|game| game.game_at(self.ply))
+    }
+}
+
+/// Right and left arrows step forward and back a move, Home and End jump to either end.
+fn step_replay(
+    keys: Res<Input<KeyCode>>,
+    mut replay: ResMut<Replay>,
+    mut game: ResMut<ChessGame>,
+    mut turn: ResMut<PlayerTurn>,
+    mut sync_event: EventWriter<SyncPiecesEvent>,
+) {
+    let (ply, last) = match &replay.game {
+        Some(pgn) => (replay.ply, pgn.moves.len()),
+        None => return,
+    };
+    let target = if keys.just_pressed(KeyCode::Right) {
+        (ply + 1).min(last)
+    } else if keys.just_pressed(KeyCode::Left) {
+        ply.saturating_sub(1)
+    } else if keys.just_pressed(KeyCode::Home) {
+        0
+    } else if keys.just_pressed(KeyCode::End) {
+        last
+    } else {
+        return;
+    };
+    if target == ply {
+        return;
+    }
+
+    replay.ply = target;
+    if let Some(pgn) = &replay.game {
+        if let Some(pgn_move) = target.checked_sub(1).map(|i| &pgn.moves[i]) {
+            match &pgn_move.comment {
+                Some(comment) => println!("{}. {} {{{}}}", target, pgn_move.san, comment),
+                None => println!("{}. {}", target, pgn_move.san),
+            }
+        }
+    }
+    if let Some(replayed) = replay.game() {
+        turn.0 = replayed.side_to_move();
+        game.0 = replayed;
+        sync_event.send(SyncPiecesEvent);
+    }
+}
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Replay>().add_system(step_replay);
+    }
+}
@@ -1,10 +1,25 @@
-use crate::board::PlayerTurn;
-use crate::pieces::PieceColor;
+use crate::board::{
+    ChessGame, GameActionEvent, HistoryEvent, HistoryView, MakeMoveEvent, NewGameEvent,
+    PendingPromotion, PlayerTurn, PromotionEvent, ViewPlyEvent,
+};
+use crate::clock::ChessClock;
+use crate::engine::{EnginePlayer, EngineTask};
+use crate::pieces::{PieceColor, PieceType};
+use crate::replay::Replay;
+use bevy::ecs::system::EntityCommands;
+use bevy::input::mouse::{MouseScrollUnit, MouseWheel};
 use bevy::prelude::*;
+use bevy::ui::FocusPolicy;
+use chess_core::{Game, GameResult, Move, Termination};
+use std::time::Duration;
 
 #[derive(Component)]
 struct NextMove;
 
+/// White's and Black's clock, in that order, next to the `NextMove` text.
+#[derive(Component)]
+struct ClockText;
+
 fn init_next_move(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
@@ -32,7 +47,7 @@ fn init_next_move(
                     text: Text::from_section(
                         "Next Move: White".to_string(),
                         TextStyle {
-                            font,
+                            font: font.clone(),
                             font_size: 40.0,
                             color: Color::rgb(0.8, 0.8, 0.8),
                         },
@@ -40,28 +55,703 @@ fn init_next_move(
                     ..Default::default()
                 })
                 .insert(NextMove);
+            let style = TextStyle {
+                font,
+                font_size: 40.0,
+                color: Color::rgb(0.8, 0.8, 0.8),
+            };
+            parent
+                .spawn(TextBundle {
+                    text: Text::from_sections([
+                        TextSection::new("", style.clone()),
+                        TextSection::new("", style),
+                    ]),
+                    ..Default::default()
+                })
+                .insert(ClockText);
         });
 }
 
-fn next_move_text(turn: Res<PlayerTurn>, mut query: Query<&mut Text, With<NextMove>>) {
-    if !turn.is_changed() {
+/// How the game ended, with the winner if there is one.
+fn result_text(result: GameResult) -> String {
+    match (result.winner, result.termination) {
+        (Some(winner), Termination::Checkmate) => {
+            format!("Checkmate, {} wins", winner)
+        }
+        (Some(winner), Termination::Timeout) => {
+            format!("{} ran out of time, {} wins", winner.opposite(), winner)
+        }
+        (Some(winner), Termination::Resignation) => {
+            format!("{} resigns, {} wins", winner.opposite(), winner)
+        }
+        (_, Termination::Stalemate) => "Stalemate".to_string(),
+        (_, termination) => format!("Draw by {}", termination),
+    }
+}
+
+fn next_move_text(
+    turn: Res<PlayerTurn>,
+    game: Res<ChessGame>,
+    engine_task: Res<EngineTask>,
+    mut query: Query<&mut Text, With<NextMove>>,
+) {
+    if !turn.is_changed() && !game.is_changed() && !engine_task.is_changed() {
         return;
     }
+    let value = match game.result() {
+        Some(result) => result_text(result),
+        None => {
+            let mut value = format!("Next move: {}", turn.0);
+            if game.is_in_check() {
+                value.push_str(" (check)");
+            }
+            if let Some(color) = game.draw_offer() {
+                value.push_str(&format!(
+                    "\n{} offers a draw, A accepts and N declines",
+                    color
+                ));
+            }
+            if let Some(termination) = game.claimable_draw() {
+                value.push_str(&format!("\nDraw by {} can be claimed with D", termination));
+            }
+            if let Some(err) = engine_task.error(game.position()) {
+                value.push_str(&format!("\nEngine failed: {}", err));
+            }
+            value
+        }
+    };
     for mut text in query.iter_mut() {
-        text.sections[0].value = format!(
-            "Next move: {}",
-            match turn.0 {
-                PieceColor::White => "White",
-                PieceColor::Black => "Black",
+        text.sections[0].value = value.clone();
+    }
+}
+
+/// `m:ss`, with tenths of a second once under ten seconds.
+fn clock_time(time: Duration) -> String {
+    let seconds = time.as_secs();
+    if seconds < 10 {
+        format!("0:0{}.{}", seconds, time.subsec_millis() / 100)
+    } else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+fn clock_text(chess_clock: Res<ChessClock>, mut query: Query<&mut Text, With<ClockText>>) {
+    if !chess_clock.is_changed() {
+        return;
+    }
+    let Some(clock) = &chess_clock.0 else {
+        return;
+    };
+    for mut text in query.iter_mut() {
+        for (section, color) in text
+            .sections
+            .iter_mut()
+            .zip([PieceColor::White, PieceColor::Black])
+        {
+            section.value = format!("  {} {}", color, clock_time(clock.remaining(color)));
+            section.style.color = if clock.flagged() == Some(color) {
+                Color::rgb(0.9, 0.1, 0.1)
+            } else if clock.running() == Some(color) {
+                Color::rgb(1., 1., 1.)
+            } else {
+                Color::rgb(0.5, 0.5, 0.5)
+            };
+        }
+    }
+}
+
+/// A button labelled `label` in the colors of all the others, `marker` tells its clicks apart.
+/// Its `Style` can be replaced for other margins or a fixed size.
+fn spawn_button<'w, 's, 'a>(
+    parent: &'a mut ChildBuilder<'w, 's, '_>,
+    font: &Handle<Font>,
+    label: impl Into<String>,
+    font_size: f32,
+    marker: impl Component,
+) -> EntityCommands<'w, 's, 'a> {
+    let mut button = parent.spawn(ButtonBundle {
+        style: Style {
+            margin: UiRect::all(Val::Px(5.)),
+            padding: UiRect::all(Val::Px(5.)),
+            ..Default::default()
+        },
+        background_color: Color::rgb(0.25, 0.25, 0.25).into(),
+        ..Default::default()
+    });
+    button.insert(marker).with_children(|button| {
+        button.spawn(TextBundle {
+            text: Text::from_section(
+                label,
+                TextStyle {
+                    font: font.clone(),
+                    font_size,
+                    color: Color::rgb(0.8, 0.8, 0.8),
+                },
+            ),
+            ..Default::default()
+        });
+    });
+    button
+}
+
+#[derive(Component)]
+struct PromotionPicker;
+
+#[derive(Component)]
+struct PromotionButton(PieceType);
+
+fn promotion_picker(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    pending_promotion: Res<PendingPromotion>,
+    picker_query: Query<Entity, With<PromotionPicker>>,
+) {
+    if !pending_promotion.is_changed() {
+        return;
+    }
+    for entity in picker_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if pending_promotion.squares.is_none() {
+        return;
+    }
+
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(10.),
+                    top: Val::Px(70.),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            background_color: Color::rgb(0.1, 0.1, 0.1).into(),
+            ..Default::default()
+        })
+        .insert(PromotionPicker)
+        .with_children(|parent| {
+            for piece_type in [
+                PieceType::Queen,
+                PieceType::Rook,
+                PieceType::Bishop,
+                PieceType::Knight,
+            ] {
+                spawn_button(
+                    parent,
+                    &font,
+                    format!("{:?}", piece_type),
+                    30.0,
+                    PromotionButton(piece_type),
+                );
+            }
+        });
+}
+
+fn promotion_buttons(
+    query: Query<(&Interaction, &PromotionButton), Changed<Interaction>>,
+    mut promotion_events: EventWriter<PromotionEvent>,
+) {
+    for (interaction, button) in query.iter() {
+        if *interaction == Interaction::Clicked {
+            promotion_events.send(PromotionEvent(button.0));
+        }
+    }
+}
+
+#[derive(Component)]
+struct HistoryButton(HistoryEvent);
+
+fn init_history_buttons(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    right: Val::Px(10.),
+                    top: Val::Px(10.),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            background_color: Color::rgb(0.1, 0.1, 0.1).into(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            for (label, event) in [("Undo", HistoryEvent::Undo), ("Redo", HistoryEvent::Redo)] {
+                spawn_button(parent, &font, label, 30.0, HistoryButton(event));
+            }
+        });
+}
+
+fn history_buttons(
+    query: Query<(&Interaction, &HistoryButton), Changed<Interaction>>,
+    mut history_events: EventWriter<HistoryEvent>,
+) {
+    for (interaction, button) in query.iter() {
+        if *interaction == Interaction::Clicked {
+            history_events.send(button.0);
+        }
+    }
+}
+
+#[derive(Component)]
+struct GameActionButton(GameActionEvent);
+
+fn init_game_action_buttons(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    right: Val::Px(10.),
+                    top: Val::Px(70.),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            background_color: Color::rgb(0.1, 0.1, 0.1).into(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            for (label, event) in [
+                ("Resign", GameActionEvent::Resign),
+                ("Offer draw", GameActionEvent::OfferDraw),
+                ("Accept", GameActionEvent::AcceptDraw),
+                ("Decline", GameActionEvent::DeclineDraw),
+            ] {
+                spawn_button(parent, &font, label, 30.0, GameActionButton(event));
             }
-        );
+        });
+}
+
+fn game_action_buttons(
+    query: Query<(&Interaction, &GameActionButton), Changed<Interaction>>,
+    mut action_events: EventWriter<GameActionEvent>,
+) {
+    for (interaction, button) in query.iter() {
+        if *interaction == Interaction::Clicked {
+            action_events.send(button.0);
+        }
+    }
+}
+
+#[derive(Component)]
+struct GameOverOverlay;
+
+#[derive(Component)]
+struct NewGameButton;
+
+/// Covers the board with the result once the game is over, until a new game is started or the
+/// last move is taken back. Replays show their result in the next move text only.
+fn game_over_overlay(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    game: Res<ChessGame>,
+    replay: Res<Replay>,
+    overlay_query: Query<Entity, With<GameOverOverlay>>,
+) {
+    let result = game.result().filter(|_| !replay.is_active());
+    if result.is_some() == !overlay_query.is_empty() {
+        return;
+    }
+    for entity in overlay_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let Some(result) = result else {
+        return;
+    };
+
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            background_color: Color::rgba(0., 0., 0., 0.6).into(),
+            // the buttons around the board stay usable underneath
+            focus_policy: FocusPolicy::Pass,
+            ..Default::default()
+        })
+        .insert(GameOverOverlay)
+        .with_children(|parent| {
+            parent.spawn(TextBundle {
+                text: Text::from_section(
+                    result_text(result),
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: 60.0,
+                        color: Color::rgb(0.8, 0.8, 0.8),
+                    },
+                ),
+                ..Default::default()
+            });
+            spawn_button(parent, &font, "New game", 40.0, NewGameButton).insert(Style {
+                margin: UiRect::all(Val::Px(20.)),
+                padding: UiRect::all(Val::Px(10.)),
+                ..Default::default()
+            });
+        });
+}
+
+fn new_game_button(
+    query: Query<&Interaction, (Changed<Interaction>, With<NewGameButton>)>,
+    mut new_game_event: EventWriter<NewGameEvent>,
+) {
+    for interaction in query.iter() {
+        if *interaction == Interaction::Clicked {
+            new_game_event.send(NewGameEvent);
+        }
+    }
+}
+
+/// A move being typed in, Enter opens the input and submits the move, Escape drops it.
+#[derive(Default, Resource)]
+pub struct MoveInput {
+    pub active: bool,
+    text: String,
+    /// Why the last submitted move was refused.
+    error: Option<String>,
+}
+
+#[derive(Component)]
+struct MoveInputText;
+
+fn init_move_input(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(10.),
+                    bottom: Val::Px(10.),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            background_color: Color::rgb(0.1, 0.1, 0.1).into(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn(TextBundle {
+                    text: Text::from_section(
+                        "",
+                        TextStyle {
+                            font,
+                            font_size: 30.0,
+                            color: Color::rgb(0.8, 0.8, 0.8),
+                        },
+                    ),
+                    ..Default::default()
+                })
+                .insert(MoveInputText);
+        });
+}
+
+/// Takes `Nf3`, `e4` or `e2e4` and plays it through `MakeMoveEvent` like a move made with the
+/// mouse.
+fn type_move(
+    keys: Res<Input<KeyCode>>,
+    mut characters: EventReader<ReceivedCharacter>,
+    mut input: ResMut<MoveInput>,
+    game: Res<ChessGame>,
+    pending_promotion: Res<PendingPromotion>,
+    replay: Res<Replay>,
+    view: Res<HistoryView>,
+    engine: Res<EnginePlayer>,
+    mut make_move_event: EventWriter<MakeMoveEvent>,
+) {
+    if !input.active {
+        characters.clear();
+        if keys.just_pressed(KeyCode::Return) {
+            input.active = true;
+        }
+        return;
+    }
+
+    if keys.just_pressed(KeyCode::Escape) {
+        *input = MoveInput::default();
+        return;
+    }
+    if keys.just_pressed(KeyCode::Back) {
+        input.text.pop();
+    }
+    for character in characters.iter() {
+        if !character.char.is_control() && !character.char.is_whitespace() {
+            input.text.push(character.char);
+        }
+    }
+    if !keys.just_pressed(KeyCode::Return) {
+        return;
+    }
+
+    let position = game.position();
+    let mv = if game.result().is_some() {
+        Err("the game is over".to_string())
+    } else if pending_promotion.squares.is_some() {
+        Err("pick a piece to promote to first".to_string())
+    } else if replay.is_active() {
+        Err("moves can't be played during a replay".to_string())
+    } else if view.is_active() {
+        Err("go back to the game first".to_string())
+    } else if engine.plays(game.side_to_move()) {
+        Err("it's the engine's move".to_string())
+    } else {
+        Move::from_uci(position, &input.text)
+            .map_or_else(|| position.parse_san(&input.text), Ok)
+            .map_err(|err| err.to_string())
+    };
+    match mv {
+        Ok(mv) => {
+            make_move_event.send(MakeMoveEvent(mv));
+            *input = MoveInput::default();
+        }
+        Err(err) => input.error = Some(err),
+    }
+}
+
+fn move_input_text(input: Res<MoveInput>, mut query: Query<&mut Text, With<MoveInputText>>) {
+    if !input.is_changed() {
+        return;
+    }
+    let value = match (&input.error, input.active) {
+        (Some(err), true) => format!("Move: {}_ ({})", input.text, err),
+        (None, true) => format!("Move: {}_", input.text),
+        (_, false) => String::new(),
+    };
+    for mut text in query.iter_mut() {
+        text.sections[0].value = value.clone();
+    }
+}
+
+/// The list of moves played, scrolled by moving it up inside its clipping parent.
+#[derive(Component, Default)]
+struct ScrollingList {
+    position: f32,
+}
+
+/// A move in the list, holding how many moves were played once it was made.
+#[derive(Component)]
+struct MoveListEntry(usize);
+
+#[derive(Component)]
+struct BackToGameButton;
+
+fn init_move_list(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    right: Val::Px(10.),
+                    top: Val::Px(130.),
+                    ..Default::default()
+                },
+                flex_direction: FlexDirection::Column,
+                ..Default::default()
+            },
+            background_color: Color::rgb(0.1, 0.1, 0.1).into(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            spawn_button(parent, &font, "Back to game", 24.0, BackToGameButton);
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(260.), Val::Px(400.)),
+                        overflow: Overflow::Hidden,
+                        flex_direction: FlexDirection::Column,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .with_children(|clip| {
+                    clip.spawn(NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Column,
+                            flex_shrink: 0.,
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    })
+                    .insert(ScrollingList::default());
+                });
+        });
+}
+
+/// Move number and White's and Black's move with the plies they lead to, a game set up with
+/// Black to move leaves White's out of the first row.
+type MoveRow = (u32, Option<(usize, String)>, Option<(usize, String)>);
+
+fn move_rows(game: &Game) -> Vec<MoveRow> {
+    let mut rows: Vec<MoveRow> = Vec::new();
+    for (i, &mv) in game.moves().enumerate() {
+        let Some(position) = game.position_at(i) else {
+            break;
+        };
+        let entry = Some((i + 1, position.san(mv)));
+        let number = position.fullmove_number();
+        match position.side_to_move() {
+            PieceColor::White => rows.push((number, entry, None)),
+            PieceColor::Black => match rows.last_mut() {
+                Some(row) if row.0 == number && row.2.is_none() => row.2 = entry,
+                _ => rows.push((number, None, entry)),
+            },
+        }
+    }
+    rows
+}
+
+fn spawn_move_entry(
+    parent: &mut ChildBuilder,
+    font: &Handle<Font>,
+    entry: Option<(usize, String)>,
+    shown: usize,
+) {
+    let style = Style {
+        size: Size::new(Val::Px(90.), Val::Auto),
+        margin: UiRect::all(Val::Px(2.)),
+        padding: UiRect::all(Val::Px(2.)),
+        ..Default::default()
+    };
+    let Some((ply, san)) = entry else {
+        parent.spawn(NodeBundle {
+            style,
+            ..Default::default()
+        });
+        return;
+    };
+    let mut button = spawn_button(parent, font, san, 24.0, MoveListEntry(ply));
+    button.insert(style);
+    if ply == shown {
+        button.insert(BackgroundColor(Color::rgb(0.5, 0.4, 0.1)));
+    }
+}
+
+/// Rebuilds the move list whenever the game or the viewed position changes.
+fn move_list(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    game: Res<ChessGame>,
+    view: Res<HistoryView>,
+    query: Query<Entity, With<ScrollingList>>,
+) {
+    if !game.is_changed() && !view.is_changed() {
+        return;
+    }
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    let shown = view.ply().unwrap_or_else(|| game.moves().count());
+    let rows = move_rows(&game);
+
+    for list in query.iter() {
+        let mut list = commands.entity(list);
+        list.despawn_descendants();
+        list.with_children(|parent| {
+            for (number, white, black) in rows.iter().cloned() {
+                parent.spawn(NodeBundle::default()).with_children(|row| {
+                    row.spawn(TextBundle {
+                        text: Text::from_section(
+                            format!("{}.", number),
+                            TextStyle {
+                                font: font.clone(),
+                                font_size: 24.0,
+                                color: Color::rgb(0.6, 0.6, 0.6),
+                            },
+                        ),
+                        style: Style {
+                            size: Size::new(Val::Px(50.), Val::Auto),
+                            margin: UiRect::all(Val::Px(4.)),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    });
+                    spawn_move_entry(row, &font, white, shown);
+                    spawn_move_entry(row, &font, black, shown);
+                });
+            }
+        });
+    }
+}
+
+fn move_list_buttons(
+    entries: Query<(&Interaction, &MoveListEntry), Changed<Interaction>>,
+    back_buttons: Query<&Interaction, (Changed<Interaction>, With<BackToGameButton>)>,
+    mut view_events: EventWriter<ViewPlyEvent>,
+) {
+    for (interaction, entry) in entries.iter() {
+        if *interaction == Interaction::Clicked {
+            view_events.send(ViewPlyEvent(Some(entry.0)));
+        }
+    }
+    for interaction in back_buttons.iter() {
+        if *interaction == Interaction::Clicked {
+            view_events.send(ViewPlyEvent(None));
+        }
+    }
+}
+
+fn scroll_move_list(
+    mut wheel_events: EventReader<MouseWheel>,
+    mut lists: Query<(&mut ScrollingList, &mut Style, &Parent, Option<&Children>)>,
+    nodes: Query<&Node>,
+) {
+    for event in wheel_events.iter() {
+        let dy = match event.unit {
+            MouseScrollUnit::Line => event.y * 20.,
+            MouseScrollUnit::Pixel => event.y,
+        };
+        for (mut list, mut style, parent, children) in lists.iter_mut() {
+            let items_height: f32 = children
+                .into_iter()
+                .flat_map(|children| children.iter())
+                .filter_map(|&child| nodes.get(child).ok())
+                .map(|node| node.size().y)
+                .sum();
+            let panel_height = nodes.get(parent.get()).map_or(0., |node| node.size().y);
+            let max_scroll = (items_height - panel_height).max(0.);
+            list.position = (list.position + dy).clamp(-max_scroll, 0.);
+            style.position.top = Val::Px(list.position);
+        }
     }
 }
 
 pub struct ChessUIPlugin;
 impl Plugin for ChessUIPlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system(init_next_move)
-            .add_system(next_move_text);
+        app.init_resource::<MoveInput>()
+            .add_startup_system(init_next_move)
+            .add_startup_system(init_move_input)
+            .add_startup_system(init_history_buttons)
+            .add_startup_system(init_game_action_buttons)
+            .add_startup_system(init_move_list)
+            .add_system(next_move_text)
+            .add_system(clock_text)
+            .add_system(type_move)
+            .add_system(move_input_text.after(type_move))
+            .add_system(promotion_picker)
+            .add_system(promotion_buttons)
+            .add_system(history_buttons)
+            .add_system(game_action_buttons)
+            .add_system(game_over_overlay)
+            .add_system(new_game_button)
+            .add_system(move_list)
+            .add_system(move_list_buttons)
+            .add_system(scroll_move_list);
     }
 }
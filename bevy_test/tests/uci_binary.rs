@@ -0,0 +1,36 @@
+//! Checks the `uci` binary hands stdin to the UCI server and its answers to stdout, the
+//! protocol itself is tested against recorded sessions in `chess_core/tests/uci_server.rs`.
+
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+
+#[test]
+fn talks_uci_over_stdio() {
+    let mut engine = Command::new(env!("CARGO_BIN_EXE_uci"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = engine.stdin.take().unwrap();
+    let mut lines = BufReader::new(engine.stdout.take().unwrap())
+        .lines()
+        .map(Result::unwrap)
+        .filter(|line| !line.starts_with("info"));
+
+    writeln!(stdin, "uci").unwrap();
+    stdin.flush().unwrap();
+    assert_eq!(lines.next().as_deref(), Some("id name bevy_test"));
+    assert_eq!(
+        lines.find(|line| !line.starts_with("id")).as_deref(),
+        Some("uciok")
+    );
+
+    writeln!(stdin, "position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+    writeln!(stdin, "go depth 3").unwrap();
+    stdin.flush().unwrap();
+    assert_eq!(lines.next().as_deref(), Some("bestmove a1a8"));
+
+    // the engine quits once stdin closes, same as on quit
+    drop(stdin);
+    assert!(engine.wait().unwrap().success());
+}
@@ -0,0 +1,28 @@
+//! Prints the perft count of every root move, `cargo run --release --example perft -- <depth> [fen]`.
+
+use chess_core::perft::divide;
+use chess_core::{Position, START_FEN};
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let depth = args.next().and_then(|d| d.parse().ok()).unwrap_or(1);
+    let fen = args.collect::<Vec<String>>().join(" ");
+    let fen = if fen.is_empty() { START_FEN } else { &fen };
+    let position = match Position::from_fen(fen) {
+        Ok(position) => position,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let divided = divide(&position, depth);
+    for (mv, nodes) in &divided {
+        println!("{}: {}", mv.to_uci(), nodes);
+    }
+    println!();
+    println!(
+        "Nodes searched: {}",
+        divided.iter().map(|(_, n)| n).sum::<u64>()
+    );
+}
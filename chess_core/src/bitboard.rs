@@ -0,0 +1,192 @@
+//! `u64` sets of squares, bit `n` standing for `Square::from_index(n)`, and the attack tables
+//! move generation is built on.
+
+use crate::piece::PieceColor;
+use crate::square::Square;
+
+pub type Bitboard = u64;
+
+pub const EMPTY: Bitboard = 0;
+/// The light squares, h1 is one of them and a1 isn't.
+pub const LIGHT_SQUARES: Bitboard = 0x55aa_55aa_55aa_55aa;
+
+const FILE_A: Bitboard = 0x0101_0101_0101_0101;
+const RANK_1: Bitboard = 0xff;
+const DIAGONAL: Bitboard = 0x8040_2010_0804_0201;
+const ANTI_DIAGONAL: Bitboard = 0x0102_0408_1020_4080;
+
+pub const fn bit(square: Square) -> Bitboard {
+    1 << square.index()
+}
+
+pub const fn rank(rank: u8) -> Bitboard {
+    RANK_1 << (rank * 8)
+}
+
+/// The squares of a bitboard, lowest index first.
+pub fn squares(mut bitboard: Bitboard) -> impl Iterator<Item = Square> {
+    std::iter::from_fn(move || {
+        if bitboard == EMPTY {
+            return None;
+        }
+        let index = bitboard.trailing_zeros() as usize;
+        bitboard &= bitboard - 1;
+        Some(Square::from_index(index))
+    })
+}
+
+const fn step_table(steps: &[(i8, i8)]) -> [Bitboard; 64] {
+    let mut table = [EMPTY; 64];
+    let mut index = 0;
+    while index < 64 {
+        let rank = (index / 8) as i8;
+        let file = (index % 8) as i8;
+        let mut i = 0;
+        while i < steps.len() {
+            let (r, f) = (rank + steps[i].0, file + steps[i].1);
+            if r >= 0 && r < 8 && f >= 0 && f < 8 {
+                table[index] |= 1 << (r * 8 + f);
+            }
+            i += 1;
+        }
+        index += 1;
+    }
+    table
+}
+
+const KNIGHT_ATTACKS: [Bitboard; 64] = step_table(&[
+    (-2, -1),
+    (-2, 1),
+    (-1, -2),
+    (-1, 2),
+    (1, -2),
+    (1, 2),
+    (2, -1),
+    (2, 1),
+]);
+
+const KING_ATTACKS: [Bitboard; 64] = step_table(&[
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+]);
+
+const WHITE_PAWN_ATTACKS: [Bitboard; 64] = step_table(&[(1, -1), (1, 1)]);
+
+const BLACK_PAWN_ATTACKS: [Bitboard; 64] = step_table(&[(-1, -1), (-1, 1)]);
+
+pub fn knight_attacks(square: Square) -> Bitboard {
+    KNIGHT_ATTACKS[square.index()]
+}
+
+pub fn king_attacks(square: Square) -> Bitboard {
+    KING_ATTACKS[square.index()]
+}
+
+/// Squares a pawn of `color` on `square` captures on.
+pub fn pawn_attacks(color: PieceColor, square: Square) -> Bitboard {
+    match color {
+        PieceColor::White => WHITE_PAWN_ATTACKS[square.index()],
+        PieceColor::Black => BLACK_PAWN_ATTACKS[square.index()],
+    }
+}
+
+/// Hyperbola quintessence: sliding attacks from `square` along the line `mask`, stopping on
+/// (and including) the first blocker in each direction.
+fn line_attacks(square: Square, occupied: Bitboard, mask: Bitboard) -> Bitboard {
+    let slider = bit(square);
+    let mask = mask & !slider;
+    let blockers = occupied & mask;
+    let forward = blockers.wrapping_sub(slider.wrapping_mul(2));
+    let reverse = blockers
+        .reverse_bits()
+        .wrapping_sub(slider.reverse_bits().wrapping_mul(2))
+        .reverse_bits();
+    (forward ^ reverse) & mask
+}
+
+fn diagonal_mask(square: Square) -> Bitboard {
+    let diagonal = square.rank() as i8 - square.file() as i8;
+    if diagonal >= 0 {
+        DIAGONAL << (diagonal * 8)
+    } else {
+        DIAGONAL >> (-diagonal * 8)
+    }
+}
+
+fn anti_diagonal_mask(square: Square) -> Bitboard {
+    let diagonal = 7 - square.rank() as i8 - square.file() as i8;
+    if diagonal >= 0 {
+        ANTI_DIAGONAL >> (diagonal * 8)
+    } else {
+        ANTI_DIAGONAL << (-diagonal * 8)
+    }
+}
+
+pub fn rook_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    line_attacks(square, occupied, FILE_A << square.file())
+        | line_attacks(square, occupied, rank(square.rank()))
+}
+
+pub fn bishop_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    line_attacks(square, occupied, diagonal_mask(square))
+        | line_attacks(square, occupied, anti_diagonal_mask(square))
+}
+
+pub fn queen_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    rook_attacks(square, occupied) | bishop_attacks(square, occupied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(squares: &[(u8, u8)]) -> Bitboard {
+        squares
+            .iter()
+            .fold(EMPTY, |bb, &(r, f)| bb | bit(Square::new(r, f)))
+    }
+
+    #[test]
+    fn leapers() {
+        assert_eq!(knight_attacks(Square::new(0, 0)), board(&[(1, 2), (2, 1)]));
+        assert_eq!(
+            king_attacks(Square::new(0, 7)),
+            board(&[(0, 6), (1, 6), (1, 7)])
+        );
+        assert_eq!(
+            pawn_attacks(PieceColor::Black, Square::new(6, 0)),
+            board(&[(5, 1)])
+        );
+    }
+
+    #[test]
+    fn sliders_stop_on_blockers() {
+        let occupied = board(&[(3, 1), (5, 3), (3, 6), (0, 3)]);
+        assert_eq!(
+            rook_attacks(Square::new(3, 3), occupied),
+            board(&[
+                (3, 2),
+                (3, 1),
+                (3, 4),
+                (3, 5),
+                (3, 6),
+                (4, 3),
+                (5, 3),
+                (2, 3),
+                (1, 3),
+                (0, 3),
+            ])
+        );
+        assert_eq!(
+            bishop_attacks(Square::new(0, 2), board(&[(2, 4)])),
+            board(&[(1, 1), (2, 0), (1, 3), (2, 4)])
+        );
+        assert_eq!(bishop_attacks(Square::new(7, 7), EMPTY).count_ones(), 7);
+    }
+}
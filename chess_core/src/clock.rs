@@ -0,0 +1,271 @@
+//! Chess clocks: a base time per player plus an increment or delay for every move.
+
+use crate::piece::PieceColor;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// What a player gets on top of the base time for each move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bonus {
+    None,
+    /// Added to the clock after every move.
+    Fischer(Duration),
+    /// Time used on a move is given back after it, up to this much.
+    Bronstein(Duration),
+    /// The clock only starts going down after this much of each move, simple delay.
+    Delay(Duration),
+}
+
+/// Longest base time or bonus a time control can be written with.
+const LONGEST: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeControl {
+    pub base: Duration,
+    pub bonus: Bonus,
+}
+
+impl TimeControl {
+    pub fn new(base: Duration, bonus: Bonus) -> Self {
+        Self { base, bonus }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimeControlError(String);
+
+impl fmt::Display for TimeControlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} isn't a time control such as 5, 5+3, 5b3 or 5d3",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for TimeControlError {}
+
+impl FromStr for TimeControl {
+    type Err = TimeControlError;
+
+    /// `5` is five minutes each, `5+3` adds three seconds a move, `5b3` gives up to three
+    /// seconds a move back (Bronstein) and `5d3` waits three seconds before the clock runs.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || TimeControlError(s.to_string());
+        let (minutes, bonus) = match s.find(|c| matches!(c, '+' | 'b' | 'd')) {
+            Some(at) => {
+                let seconds = s[at + 1..].parse::<f64>().map_err(|_| err())?;
+                let seconds = Duration::try_from_secs_f64(seconds)
+                    .ok()
+                    .filter(|&seconds| seconds <= LONGEST)
+                    .ok_or_else(err)?;
+                let bonus = match &s[at..at + 1] {
+                    "+" => Bonus::Fischer(seconds),
+                    "b" => Bonus::Bronstein(seconds),
+                    _ => Bonus::Delay(seconds),
+                };
+                (&s[..at], bonus)
+            }
+            None => (s, Bonus::None),
+        };
+        let minutes = minutes.parse::<f64>().map_err(|_| err())?;
+        match Duration::try_from_secs_f64(minutes * 60.) {
+            Ok(base) if !base.is_zero() && base <= LONGEST => Ok(Self::new(base, bonus)),
+            _ => Err(err()),
+        }
+    }
+}
+
+/// Both players' clocks, at most one of them running.
+#[derive(Debug, Clone)]
+pub struct Clock {
+    control: TimeControl,
+    /// White's and Black's time left, by `PieceColor::index`.
+    remaining: [Duration; 2],
+    running: Option<PieceColor>,
+    /// Time the running side spent on the current move.
+    spent: Duration,
+    flagged: Option<PieceColor>,
+}
+
+impl Clock {
+    pub fn new(control: TimeControl) -> Self {
+        Self {
+            control,
+            remaining: [control.base; 2],
+            running: None,
+            spent: Duration::ZERO,
+            flagged: None,
+        }
+    }
+
+    pub fn control(&self) -> TimeControl {
+        self.control
+    }
+
+    pub fn remaining(&self, color: PieceColor) -> Duration {
+        self.remaining[color.index()]
+    }
+
+    pub fn running(&self) -> Option<PieceColor> {
+        self.running
+    }
+
+    /// The side whose time ran out, the clock stays stopped after that.
+    pub fn flagged(&self) -> Option<PieceColor> {
+        self.flagged
+    }
+
+    /// Runs `color`'s clock from the start of a move, without any bonus for the other side.
+    pub fn start(&mut self, color: PieceColor) {
+        if self.flagged.is_none() {
+            self.running = Some(color);
+            self.spent = Duration::ZERO;
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.running = None;
+    }
+
+    /// Counts `elapsed` against the running side, returning it if that made its time run out.
+    pub fn tick(&mut self, elapsed: Duration) -> Option<PieceColor> {
+        let color = self.running?;
+        let charged = match self.control.bonus {
+            Bonus::Delay(delay) => {
+                (self.spent + elapsed).saturating_sub(delay) - self.spent.saturating_sub(delay)
+            }
+            _ => elapsed,
+        };
+        self.spent += elapsed;
+        let remaining = &mut self.remaining[color.index()];
+        *remaining = remaining.saturating_sub(charged);
+        if remaining.is_zero() {
+            self.running = None;
+            self.flagged = Some(color);
+        }
+        self.flagged
+    }
+
+    /// The running side finished its move: its bonus is added and the other clock starts.
+    pub fn press(&mut self) {
+        let Some(color) = self.running else {
+            return;
+        };
+        let bonus = match self.control.bonus {
+            Bonus::Fischer(increment) => increment,
+            Bonus::Bronstein(delay) => self.spent.min(delay),
+            Bonus::None | Bonus::Delay(_) => Duration::ZERO,
+        };
+        let remaining = &mut self.remaining[color.index()];
+        *remaining = remaining.saturating_add(bonus);
+        self.start(color.opposite());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(seconds: u64) -> Duration {
+        Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn parses_time_controls() {
+        let parse = |s: &str| s.parse::<TimeControl>();
+        assert_eq!(parse("5"), Ok(TimeControl::new(secs(300), Bonus::None)));
+        assert_eq!(
+            parse("3+2"),
+            Ok(TimeControl::new(secs(180), Bonus::Fischer(secs(2))))
+        );
+        assert_eq!(
+            parse("0.5b1.5"),
+            Ok(TimeControl::new(
+                secs(30),
+                Bonus::Bronstein(Duration::from_millis(1500))
+            ))
+        );
+        assert_eq!(
+            parse("90d30"),
+            Ok(TimeControl::new(secs(5400), Bonus::Delay(secs(30))))
+        );
+        for invalid in [
+            "", "+2", "5+", "0", "-1", "5x3", "5+-1", "inf", "1e300", "5+1e300", "5+1e18", "1441",
+        ] {
+            assert!(parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn fischer() {
+        let mut clock = Clock::new(TimeControl::new(secs(60), Bonus::Fischer(secs(2))));
+        clock.start(PieceColor::White);
+        clock.tick(secs(5));
+        clock.press();
+        assert_eq!(clock.remaining(PieceColor::White), secs(57));
+        assert_eq!(clock.running(), Some(PieceColor::Black));
+        clock.tick(secs(1));
+        clock.press();
+        assert_eq!(clock.remaining(PieceColor::Black), secs(61));
+    }
+
+    #[test]
+    fn huge_increment() {
+        let mut clock = Clock::new(TimeControl::new(secs(60), Bonus::Fischer(Duration::MAX)));
+        clock.start(PieceColor::White);
+        for _ in 0..4 {
+            clock.tick(secs(1));
+            clock.press();
+        }
+        assert_eq!(clock.remaining(PieceColor::White), Duration::MAX);
+    }
+
+    #[test]
+    fn bronstein() {
+        let mut clock = Clock::new(TimeControl::new(secs(60), Bonus::Bronstein(secs(3))));
+        clock.start(PieceColor::White);
+        clock.tick(secs(2));
+        clock.press();
+        assert_eq!(clock.remaining(PieceColor::White), secs(60));
+        clock.tick(secs(10));
+        clock.press();
+        assert_eq!(clock.remaining(PieceColor::Black), secs(53));
+    }
+
+    #[test]
+    fn delay() {
+        let mut clock = Clock::new(TimeControl::new(secs(60), Bonus::Delay(secs(3))));
+        clock.start(PieceColor::White);
+        clock.tick(secs(2));
+        assert_eq!(clock.remaining(PieceColor::White), secs(60));
+        clock.tick(secs(2));
+        assert_eq!(clock.remaining(PieceColor::White), secs(59));
+        clock.press();
+        // the delay starts over with every move
+        clock.tick(secs(3));
+        clock.press();
+        clock.tick(secs(4));
+        assert_eq!(clock.remaining(PieceColor::Black), secs(60));
+        assert_eq!(clock.remaining(PieceColor::White), secs(58));
+    }
+
+    #[test]
+    fn flag_falls() {
+        let mut clock = Clock::new(TimeControl::new(secs(10), Bonus::Fischer(secs(5))));
+        clock.start(PieceColor::Black);
+        assert_eq!(clock.tick(secs(9)), None);
+        assert_eq!(clock.tick(secs(2)), Some(PieceColor::Black));
+        assert_eq!(clock.remaining(PieceColor::Black), Duration::ZERO);
+        assert_eq!(clock.running(), None);
+        // a fallen flag stays down
+        clock.press();
+        clock.start(PieceColor::White);
+        assert_eq!(clock.running(), None);
+        assert_eq!(clock.tick(secs(1)), None);
+        assert_eq!(clock.flagged(), Some(PieceColor::Black));
+        assert_eq!(clock.remaining(PieceColor::White), secs(10));
+    }
+}
@@ -0,0 +1,133 @@
+//! Static evaluation: material plus piece-square tables, in centipawns.
+
+use crate::bitboard;
+use crate::piece::{PieceColor, PieceType};
+use crate::position::Position;
+
+pub fn piece_value(piece_type: PieceType) -> i32 {
+    match piece_type {
+        PieceType::Pawn => 100,
+        PieceType::Knight => 320,
+        PieceType::Bishop => 330,
+        PieceType::Rook => 500,
+        PieceType::Queen => 900,
+        PieceType::King => 20_000,
+    }
+}
+
+// Tables are laid out the way the board is drawn from White's side, eighth rank first.
+#[rustfmt::skip]
+const PAWN_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    10, 10, 20, 30, 30, 20, 10, 10,
+     5,  5, 10, 25, 25, 10,  5,  5,
+     0,  0,  0, 20, 20,  0,  0,  0,
+     5, -5,-10,  0,  0,-10, -5,  5,
+     5, 10, 10,-20,-20, 10, 10,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const KNIGHT_TABLE: [i32; 64] = [
+    -50,-40,-30,-30,-30,-30,-40,-50,
+    -40,-20,  0,  0,  0,  0,-20,-40,
+    -30,  0, 10, 15, 15, 10,  0,-30,
+    -30,  5, 15, 20, 20, 15,  5,-30,
+    -30,  0, 15, 20, 20, 15,  0,-30,
+    -30,  5, 10, 15, 15, 10,  5,-30,
+    -40,-20,  0,  5,  5,  0,-20,-40,
+    -50,-40,-30,-30,-30,-30,-40,-50,
+];
+
+#[rustfmt::skip]
+const BISHOP_TABLE: [i32; 64] = [
+    -20,-10,-10,-10,-10,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5, 10, 10,  5,  0,-10,
+    -10,  5,  5, 10, 10,  5,  5,-10,
+    -10,  0, 10, 10, 10, 10,  0,-10,
+    -10, 10, 10, 10, 10, 10, 10,-10,
+    -10,  5,  0,  0,  0,  0,  5,-10,
+    -20,-10,-10,-10,-10,-10,-10,-20,
+];
+
+#[rustfmt::skip]
+const ROOK_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+     5, 10, 10, 10, 10, 10, 10,  5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+     0,  0,  0,  5,  5,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const QUEEN_TABLE: [i32; 64] = [
+    -20,-10,-10, -5, -5,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5,  5,  5,  5,  0,-10,
+     -5,  0,  5,  5,  5,  5,  0, -5,
+      0,  0,  5,  5,  5,  5,  0, -5,
+    -10,  5,  5,  5,  5,  5,  0,-10,
+    -10,  0,  5,  0,  0,  0,  0,-10,
+    -20,-10,-10, -5, -5,-10,-10,-20,
+];
+
+#[rustfmt::skip]
+const KING_TABLE: [i32; 64] = [
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -20,-30,-30,-40,-40,-30,-30,-20,
+    -10,-20,-20,-20,-20,-20,-20,-10,
+     20, 20,  0,  0,  0,  0, 20, 20,
+     20, 30, 10,  0,  0, 10, 30, 20,
+];
+
+fn table(piece_type: PieceType) -> &'static [i32; 64] {
+    match piece_type {
+        PieceType::Pawn => &PAWN_TABLE,
+        PieceType::Knight => &KNIGHT_TABLE,
+        PieceType::Bishop => &BISHOP_TABLE,
+        PieceType::Rook => &ROOK_TABLE,
+        PieceType::Queen => &QUEEN_TABLE,
+        PieceType::King => &KING_TABLE,
+    }
+}
+
+/// How good the position is for the side to move.
+pub fn evaluate(position: &Position) -> i32 {
+    let mut score = 0;
+    for piece_type in PieceType::ALL {
+        let table = table(piece_type);
+        let value = piece_value(piece_type);
+        for square in bitboard::squares(position.bitboard(piece_type, PieceColor::White)) {
+            score += value + table[(7 - square.rank() as usize) * 8 + square.file() as usize];
+        }
+        for square in bitboard::squares(position.bitboard(piece_type, PieceColor::Black)) {
+            score -= value + table[square.index()];
+        }
+    }
+    match position.side_to_move() {
+        PieceColor::White => score,
+        PieceColor::Black => -score,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn symmetric() {
+        assert_eq!(evaluate(&Position::start()), 0);
+        let white = Position::from_fen("4k3/8/8/8/8/8/3P4/R3K3 w - - 0 1").unwrap();
+        let black = Position::from_fen("r3k3/3p4/8/8/8/8/8/4K3 b - - 0 1").unwrap();
+        assert_eq!(evaluate(&white), evaluate(&black));
+        assert!(evaluate(&white) > 500);
+    }
+}
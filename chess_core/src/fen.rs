@@ -0,0 +1,315 @@
+//! Forsyth-Edwards Notation, one line describing a whole position.
+
+use crate::piece::{Piece, PieceColor, PieceType};
+use crate::position::{CastlingRights, Position};
+use crate::square::Square;
+use std::fmt;
+
+pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FenError {
+    /// Fewer than the four required fields, the move counters may be left out.
+    MissingField(&'static str),
+    InvalidPlacement(String),
+    InvalidSideToMove(String),
+    InvalidCastling(String),
+    InvalidEnPassant(String),
+    InvalidCounter(String),
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FenError::MissingField(field) => write!(f, "FEN is missing the {} field", field),
+            FenError::InvalidPlacement(s) => write!(f, "invalid piece placement {:?}", s),
+            FenError::InvalidSideToMove(s) => write!(f, "invalid side to move {:?}", s),
+            FenError::InvalidCastling(s) => write!(f, "invalid castling rights {:?}", s),
+            FenError::InvalidEnPassant(s) => write!(f, "invalid en passant square {:?}", s),
+            FenError::InvalidCounter(s) => write!(f, "invalid move counter {:?}", s),
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+impl Position {
+    pub fn from_fen(fen: &str) -> Result<Position, FenError> {
+        let mut fields = fen.split_whitespace();
+        let mut position = Position::empty();
+
+        let placement = fields
+            .next()
+            .ok_or(FenError::MissingField("piece placement"))?;
+        let ranks = placement.split('/').collect::<Vec<&str>>();
+        if ranks.len() != 8 {
+            return Err(FenError::InvalidPlacement(placement.to_string()));
+        }
+        // FEN lists the eighth rank first
+        for (rank, row) in (0..8).rev().zip(ranks) {
+            let mut file = 0;
+            for c in row.chars() {
+                if let Some(empty) = c.to_digit(10).filter(|n| (1..=8).contains(n)) {
+                    if file + empty as u8 > 8 {
+                        return Err(FenError::InvalidPlacement(placement.to_string()));
+                    }
+                    file += empty as u8;
+                } else if let Some(piece) = Piece::from_char(c).filter(|_| file < 8) {
+                    position.put(Square::new(rank, file), piece);
+                    file += 1;
+                } else {
+                    return Err(FenError::InvalidPlacement(placement.to_string()));
+                }
+            }
+            if file != 8 {
+                return Err(FenError::InvalidPlacement(placement.to_string()));
+            }
+        }
+
+        let side_to_move = fields
+            .next()
+            .ok_or(FenError::MissingField("side to move"))?;
+        position.set_side_to_move(match side_to_move {
+            "w" => PieceColor::White,
+            "b" => PieceColor::Black,
+            _ => return Err(FenError::InvalidSideToMove(side_to_move.to_string())),
+        });
+
+        let castling = fields.next().ok_or(FenError::MissingField("castling"))?;
+        let mut rights = CastlingRights::NONE;
+        if castling != "-" {
+            for c in castling.chars() {
+                match c {
+                    'K' => rights.white_king_side = true,
+                    'Q' => rights.white_queen_side = true,
+                    'k' => rights.black_king_side = true,
+                    'q' => rights.black_queen_side = true,
+                    _ => return Err(FenError::InvalidCastling(castling.to_string())),
+                }
+            }
+        }
+        position.set_castling(rights);
+
+        let en_passant = fields.next().ok_or(FenError::MissingField("en passant"))?;
+        if en_passant != "-" {
+            let invalid = || FenError::InvalidEnPassant(en_passant.to_string());
+            let square = en_passant.parse::<Square>().map_err(|_| invalid())?;
+            // the square a pawn just skipped, with that pawn right in front of it
+            let side = position.side_to_move();
+            let (skipped_rank, pawn_rank) = match side {
+                PieceColor::White => (5, 4),
+                PieceColor::Black => (2, 3),
+            };
+            let pawn = Piece::new(PieceType::Pawn, side.opposite());
+            if square.rank() != skipped_rank
+                || position.piece_at(Square::new(pawn_rank, square.file())) != Some(pawn)
+            {
+                return Err(invalid());
+            }
+            position.set_en_passant(Some(square));
+        }
+
+        let mut counter = |default: u32| match fields.next() {
+            Some(field) => field
+                .parse::<u32>()
+                .map_err(|_| FenError::InvalidCounter(field.to_string())),
+            None => Ok(default),
+        };
+        position.set_halfmove_clock(counter(0)?);
+        position.set_fullmove_number(counter(1)?);
+
+        for color in [PieceColor::White, PieceColor::Black] {
+            if position.bitboard(PieceType::King, color).count_ones() != 1 {
+                return Err(FenError::InvalidPlacement(placement.to_string()));
+            }
+        }
+        Ok(position)
+    }
+
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+            for file in 0..8 {
+                match self.piece_at(Square::new(rank, file)) {
+                    Some(piece) => {
+                        if empty > 0 {
+                            fen.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        fen.push(piece.to_char());
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if rank > 0 {
+                fen.push('/');
+            }
+        }
+
+        fen.push(' ');
+        fen.push(match self.side_to_move() {
+            PieceColor::White => 'w',
+            PieceColor::Black => 'b',
+        });
+
+        fen.push(' ');
+        let castling = self.castling();
+        let rights = [
+            (castling.white_king_side, 'K'),
+            (castling.white_queen_side, 'Q'),
+            (castling.black_king_side, 'k'),
+            (castling.black_queen_side, 'q'),
+        ];
+        if rights.iter().any(|&(allowed, _)| allowed) {
+            fen.extend(
+                rights
+                    .iter()
+                    .filter(|&&(allowed, _)| allowed)
+                    .map(|&(_, c)| c),
+            );
+        } else {
+            fen.push('-');
+        }
+
+        fen.push(' ');
+        match self.en_passant() {
+            Some(square) => fen.push_str(&square.to_string()),
+            None => fen.push('-'),
+        }
+
+        fen.push_str(&format!(
+            " {} {}",
+            self.halfmove_clock(),
+            self.fullmove_number()
+        ));
+        fen
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn start_position() {
+        assert_eq!(Position::from_fen(START_FEN), Ok(Position::start()));
+        assert_eq!(Position::start().to_fen(), START_FEN);
+    }
+
+    #[test]
+    fn round_trip() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w Kq d6 0 3",
+            "4k3/8/8/8/8/8/8/4K3 b - - 37 80",
+        ] {
+            assert_eq!(Position::from_fen(fen).unwrap().to_fen(), fen);
+        }
+    }
+
+    #[test]
+    fn fields() {
+        let position =
+            Position::from_fen("rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w Kq d6 0 3")
+                .unwrap();
+        assert_eq!(
+            position.piece_at(Square::new(4, 3)),
+            Some(Piece::new(PieceType::Pawn, PieceColor::Black))
+        );
+        assert_eq!(position.en_passant(), Some(Square::new(5, 3)));
+        assert_eq!(
+            position.castling().for_color(PieceColor::White),
+            (true, false)
+        );
+        assert_eq!(
+            position.castling().for_color(PieceColor::Black),
+            (false, true)
+        );
+        assert_eq!(position.fullmove_number(), 3);
+    }
+
+    #[test]
+    fn counters_default_when_left_out() {
+        let position = Position::from_fen("4k3/8/8/8/8/8/8/4K3 w - -").unwrap();
+        assert_eq!(position.halfmove_clock(), 0);
+        assert_eq!(position.fullmove_number(), 1);
+    }
+
+    #[test]
+    fn counters_follow_moves() {
+        let mut position = Position::start();
+        for (from, to) in [("g1", "f3"), ("g8", "f6"), ("e2", "e4")] {
+            let mv = position
+                .legal_move(from.parse().unwrap(), to.parse().unwrap(), None)
+                .unwrap();
+            position.make_move(mv);
+        }
+        assert_eq!(
+            position.to_fen(),
+            "rnbqkb1r/pppppppp/5n2/8/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq e3 0 2"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            Position::from_fen(""),
+            Err(FenError::MissingField("piece placement"))
+        );
+        assert!(matches!(
+            Position::from_fen("rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+            Err(FenError::InvalidPlacement(_))
+        ));
+        assert!(matches!(
+            Position::from_fen("rnbqkbnr/pppppppp/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+            Err(FenError::InvalidPlacement(_))
+        ));
+        assert!(matches!(
+            Position::from_fen("8/8/8/8/8/8/8/8 x - - 0 1"),
+            Err(FenError::InvalidSideToMove(_))
+        ));
+        assert!(matches!(
+            Position::from_fen("8/8/8/8/8/8/8/8 w KX - 0 1"),
+            Err(FenError::InvalidCastling(_))
+        ));
+        assert!(matches!(
+            Position::from_fen("8/8/8/8/8/8/8/8 w - e9 0 1"),
+            Err(FenError::InvalidEnPassant(_))
+        ));
+        assert!(matches!(
+            Position::from_fen("8/8/8/8/8/8/8/8 w - - x 1"),
+            Err(FenError::InvalidCounter(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_impossible_positions() {
+        let invalid_placement =
+            |fen| matches!(Position::from_fen(fen), Err(FenError::InvalidPlacement(_)));
+        // digits adding up past the end of the rank
+        assert!(invalid_placement(
+            "4k3/8/8/8/8/8/8/4K3888888888888888888888888888888888 w - - 0 1"
+        ));
+        // one king a side
+        assert!(invalid_placement("8/8/8/8/8/8/8/4K3 w - - 0 1"));
+        assert!(invalid_placement("4k3/8/8/8/8/8/8/8 w - - 0 1"));
+        assert!(invalid_placement("4k3/8/8/8/8/8/8/3KK3 w - - 0 1"));
+
+        let invalid_en_passant =
+            |fen| matches!(Position::from_fen(fen), Err(FenError::InvalidEnPassant(_)));
+        // on the wrong rank for the side to move
+        assert!(invalid_en_passant("4k3/8/8/8/4P3/8/8/4K3 w - e4 0 1"));
+        assert!(invalid_en_passant("4k3/8/8/8/4P3/8/8/4K3 w - e3 0 1"));
+        assert!(invalid_en_passant("4k3/8/8/8/8/8/8/4K3 w - a8 0 1"));
+        // without the pawn that skipped it
+        assert!(invalid_en_passant("4k3/8/8/8/8/8/8/4K3 w - d6 0 1"));
+        assert!(invalid_en_passant("4k3/8/8/8/8/8/8/4K3 b - e3 0 1"));
+        assert!(invalid_en_passant("4k3/8/8/3P4/8/8/8/4K3 w - d6 0 1"));
+        assert!(Position::from_fen("4k3/8/8/8/4P3/8/8/4K3 b - e3 0 1").is_ok());
+    }
+}
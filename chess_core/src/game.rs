@@ -0,0 +1,652 @@
+use crate::moves::Move;
+use crate::piece::PieceColor;
+use crate::position::Position;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Termination {
+    Checkmate,
+    Stalemate,
+    /// Neither side has the pieces left to ever give mate.
+    InsufficientMaterial,
+    /// Claimed once the position came up for the third time.
+    ThreefoldRepetition,
+    /// Claimed after fifty moves by each side without a capture or pawn move.
+    FiftyMoveRule,
+    /// The position came up for the fifth time, a draw without anyone claiming it.
+    FivefoldRepetition,
+    /// Seventy-five moves by each side without a capture or pawn move, a draw without anyone
+    /// claiming it.
+    SeventyFiveMoveRule,
+    /// The loser gave up.
+    Resignation,
+    /// One side offered a draw and the other accepted.
+    DrawAgreement,
+    /// The loser's clock ran out.
+    Timeout,
+    /// A clock ran out but the other side couldn't have mated anyway.
+    TimeoutVsInsufficientMaterial,
+}
+
+impl fmt::Display for Termination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Termination::Checkmate => "checkmate",
+            Termination::Stalemate => "stalemate",
+            Termination::InsufficientMaterial => "insufficient material",
+            Termination::ThreefoldRepetition => "threefold repetition",
+            Termination::FiftyMoveRule => "the fifty-move rule",
+            Termination::FivefoldRepetition => "fivefold repetition",
+            Termination::SeventyFiveMoveRule => "the seventy-five-move rule",
+            Termination::Resignation => "resignation",
+            Termination::DrawAgreement => "agreement",
+            Termination::Timeout => "time",
+            Termination::TimeoutVsInsufficientMaterial => "timeout against insufficient material",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Final outcome of a game, `winner` is `None` for a draw.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GameResult {
+    pub winner: Option<PieceColor>,
+    pub termination: Termination,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MoveError {
+    /// The game already has a result.
+    GameOver,
+    /// The move isn't legal in the current position.
+    Illegal(Move),
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoveError::GameOver => write!(f, "the game is already over"),
+            MoveError::Illegal(mv) => write!(f, "illegal move {}{}", mv.from, mv.to),
+        }
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+/// A game in progress: the current position, the moves that led to it and the result once the
+/// side to move runs out of legal moves or the game is drawn.
+#[derive(Debug, Clone)]
+pub struct Game {
+    position: Position,
+    /// Each move played together with the position it was played in.
+    history: Vec<(Position, Move)>,
+    /// Moves taken back, most recent last, until a different move is played.
+    undone: Vec<Move>,
+    /// Side whose draw offer is waiting on an answer, the other side moving declines it.
+    draw_offer: Option<PieceColor>,
+    result: Option<GameResult>,
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Self::new(Position::start())
+    }
+}
+
+impl Game {
+    pub fn new(position: Position) -> Self {
+        let mut game = Self {
+            position,
+            history: Vec::new(),
+            undone: Vec::new(),
+            draw_offer: None,
+            result: None,
+        };
+        game.update_result();
+        game
+    }
+
+    pub fn position(&self) -> &Position {
+        &self.position
+    }
+
+    /// The position the game started from.
+    pub fn initial_position(&self) -> Position {
+        self.history
+            .first()
+            .map_or(self.position, |&(position, _)| position)
+    }
+
+    /// The position after the first `ply` moves, `None` past the moves played.
+    pub fn position_at(&self, ply: usize) -> Option<Position> {
+        match self.history.get(ply) {
+            Some(&(position, _)) => Some(position),
+            None if ply == self.history.len() => Some(self.position),
+            None => None,
+        }
+    }
+
+    pub fn side_to_move(&self) -> PieceColor {
+        self.position.side_to_move()
+    }
+
+    pub fn is_in_check(&self) -> bool {
+        self.position.is_in_check(self.side_to_move())
+    }
+
+    pub fn result(&self) -> Option<GameResult> {
+        self.result
+    }
+
+    /// The moves played so far, oldest first.
+    pub fn moves(&self) -> impl Iterator<Item = &Move> + '_ {
+        self.history.iter().map(|(_, mv)| mv)
+    }
+
+    /// Hashes of the positions before the current one, oldest first.
+    pub fn previous_hashes(&self) -> impl Iterator<Item = u64> + '_ {
+        self.history.iter().map(|(position, _)| position.hash())
+    }
+
+    /// How many times the current position came up before, with the same side to move and the
+    /// same castling and en passant options.
+    pub fn repetitions(&self) -> usize {
+        let hash = self.position.hash();
+        self.previous_hashes()
+            .filter(|&earlier| earlier == hash)
+            .count()
+    }
+
+    /// The draw the side to move may claim, by threefold repetition or the fifty-move rule.
+    pub fn claimable_draw(&self) -> Option<Termination> {
+        if self.result.is_some() {
+            None
+        } else if self.repetitions() >= 2 {
+            Some(Termination::ThreefoldRepetition)
+        } else if self.position.halfmove_clock() >= 100 {
+            Some(Termination::FiftyMoveRule)
+        } else {
+            None
+        }
+    }
+
+    /// Ends the game in a draw if one can be claimed, see `claimable_draw`.
+    pub fn claim_draw(&mut self) -> Option<GameResult> {
+        let termination = self.claimable_draw()?;
+        self.finish(None, termination)
+    }
+
+    /// Ends the game with `color` giving up.
+    pub fn resign(&mut self, color: PieceColor) -> Option<GameResult> {
+        self.finish(Some(color.opposite()), Termination::Resignation)
+    }
+
+    /// Ends the game with `color` out of time, drawn when the other side couldn't mate anyway.
+    pub fn flag(&mut self, color: PieceColor) -> Option<GameResult> {
+        let opponent = color.opposite();
+        if !self.position.can_mate(opponent) {
+            self.finish(None, Termination::TimeoutVsInsufficientMaterial)
+        } else {
+            self.finish(Some(opponent), Termination::Timeout)
+        }
+    }
+
+    pub fn draw_offer(&self) -> Option<PieceColor> {
+        self.draw_offer
+    }
+
+    /// Offers the other side a draw, `false` once the game is over.
+    pub fn offer_draw(&mut self, color: PieceColor) -> bool {
+        if self.result.is_some() {
+            return false;
+        }
+        self.draw_offer = Some(color);
+        true
+    }
+
+    /// Takes up the pending draw offer, if there is one.
+    pub fn accept_draw(&mut self) -> Option<GameResult> {
+        self.draw_offer?;
+        self.finish(None, Termination::DrawAgreement)
+    }
+
+    /// Turns down the pending draw offer, `false` if there wasn't one.
+    pub fn decline_draw(&mut self) -> bool {
+        self.draw_offer.take().is_some()
+    }
+
+    /// Legal moves for the side to move, none once the game is over.
+    pub fn legal_moves(&self) -> Vec<Move> {
+        if self.result.is_some() {
+            return Vec::new();
+        }
+        self.position.legal_moves()
+    }
+
+    pub fn make_move(&mut self, mv: Move) -> Result<(), MoveError> {
+        if self.result.is_some() {
+            return Err(MoveError::GameOver);
+        }
+        if !self.position.legal_moves().contains(&mv) {
+            return Err(MoveError::Illegal(mv));
+        }
+
+        self.play(mv);
+        self.undone.clear();
+        Ok(())
+    }
+
+    /// Takes back the last move, returning it.
+    pub fn undo(&mut self) -> Option<Move> {
+        let (before, mv) = self.history.pop()?;
+        self.position = before;
+        self.undone.push(mv);
+        self.draw_offer = None;
+        self.result = None;
+        self.update_result();
+        Some(mv)
+    }
+
+    /// Plays the last move taken back again, returning it.
+    pub fn redo(&mut self) -> Option<Move> {
+        let mv = self.undone.pop()?;
+        self.play(mv);
+        Some(mv)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.history.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.undone.is_empty()
+    }
+
+    fn play(&mut self, mv: Move) {
+        let before = self.position;
+        self.position.make_move(mv);
+        self.history.push((before, mv));
+        if self.draw_offer == Some(self.side_to_move()) {
+            self.draw_offer = None;
+        }
+        self.update_result();
+    }
+
+    /// Ends the game unless it is already over.
+    fn finish(
+        &mut self,
+        winner: Option<PieceColor>,
+        termination: Termination,
+    ) -> Option<GameResult> {
+        if self.result.is_some() {
+            return None;
+        }
+        self.draw_offer = None;
+        // moves taken back don't get played onto a finished game
+        self.undone.clear();
+        self.result = Some(GameResult {
+            winner,
+            termination,
+        });
+        self.result
+    }
+
+    fn update_result(&mut self) {
+        let termination = if !self.position.legal_moves().is_empty() {
+            if self.position.has_insufficient_material() {
+                Termination::InsufficientMaterial
+            } else if self.position.halfmove_clock() >= 150 {
+                Termination::SeventyFiveMoveRule
+            } else if self.repetitions() >= 4 {
+                Termination::FivefoldRepetition
+            } else {
+                return;
+            }
+        } else if self.is_in_check() {
+            Termination::Checkmate
+        } else {
+            Termination::Stalemate
+        };
+        let winner = match termination {
+            Termination::Checkmate => Some(self.side_to_move().opposite()),
+            _ => None,
+        };
+        self.finish(winner, termination);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::piece::{Piece, PieceType};
+    use crate::square::Square;
+
+    /// (rank, file) of the from and to squares.
+    type Played = ((u8, u8), (u8, u8));
+
+    fn play(game: &mut Game, moves: &[Played]) {
+        for &(from, to) in moves {
+            let mv = game
+                .position()
+                .legal_move(Square::new(from.0, from.1), Square::new(to.0, to.1), None)
+                .unwrap();
+            game.make_move(mv).unwrap();
+        }
+    }
+
+    #[test]
+    fn fools_mate() {
+        let mut game = Game::default();
+        // f3 e5 g4 Qh4#
+        play(
+            &mut game,
+            &[
+                ((1, 5), (2, 5)),
+                ((6, 4), (4, 4)),
+                ((1, 6), (3, 6)),
+                ((7, 3), (3, 7)),
+            ],
+        );
+
+        assert!(game.is_in_check());
+        assert_eq!(
+            game.result(),
+            Some(GameResult {
+                winner: Some(PieceColor::Black),
+                termination: Termination::Checkmate,
+            })
+        );
+        assert!(game.legal_moves().is_empty());
+        assert_eq!(game.moves().count(), 4);
+    }
+
+    #[test]
+    fn stalemate() {
+        let mut position = Position::empty();
+        position.put(
+            Square::new(7, 0),
+            Piece::new(PieceType::King, PieceColor::Black),
+        );
+        position.put(
+            Square::new(5, 1),
+            Piece::new(PieceType::Queen, PieceColor::White),
+        );
+        position.put(
+            Square::new(0, 7),
+            Piece::new(PieceType::King, PieceColor::White),
+        );
+        position.set_side_to_move(PieceColor::Black);
+
+        let game = Game::new(position);
+        assert!(!game.is_in_check());
+        assert_eq!(
+            game.result(),
+            Some(GameResult {
+                winner: None,
+                termination: Termination::Stalemate,
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_illegal_moves() {
+        let mut game = Game::default();
+        let mv = game.legal_moves()[0];
+        game.make_move(mv).unwrap();
+        assert_eq!(game.make_move(mv), Err(MoveError::Illegal(mv)));
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let mut game = Game::default();
+        // f3 e5 g4 Qh4#
+        play(
+            &mut game,
+            &[
+                ((1, 5), (2, 5)),
+                ((6, 4), (4, 4)),
+                ((1, 6), (3, 6)),
+                ((7, 3), (3, 7)),
+            ],
+        );
+        let mated = *game.position();
+        assert_eq!(game.position_at(0), Some(Position::start()));
+        assert_eq!(game.position_at(4), Some(mated));
+        assert_eq!(game.position_at(5), None);
+
+        let mate = game.undo().unwrap();
+        assert_eq!(game.result(), None);
+        assert_eq!(game.side_to_move(), PieceColor::Black);
+        assert_eq!(game.redo(), Some(mate));
+        assert_eq!(game.position(), &mated);
+        assert!(game.result().is_some());
+
+        while game.undo().is_some() {}
+        assert_eq!(game.position(), &Position::start());
+        assert!(!game.can_undo());
+
+        // ending the game some other way drops the moves taken back
+        let mut resigned = game.clone();
+        resigned.resign(PieceColor::White);
+        assert!(!resigned.can_redo());
+        assert_eq!(resigned.redo(), None);
+        assert_eq!(
+            resigned.result().unwrap().termination,
+            Termination::Resignation
+        );
+
+        // a new move drops the moves that were taken back
+        play(&mut game, &[((1, 4), (3, 4))]);
+        assert!(!game.can_redo());
+        assert_eq!(game.redo(), None);
+    }
+
+    #[test]
+    fn repetitions() {
+        let mut game = Game::default();
+        // Nf3 Nf6 Ng1 Ng8, twice
+        let knights = [
+            ((0, 6), (2, 5)),
+            ((7, 6), (5, 5)),
+            ((2, 5), (0, 6)),
+            ((5, 5), (7, 6)),
+        ];
+        play(&mut game, &knights);
+        assert_eq!(game.repetitions(), 1);
+        play(&mut game, &knights[..1]);
+        assert_eq!(game.repetitions(), 1);
+        play(&mut game, &knights[1..]);
+        assert_eq!(game.repetitions(), 2);
+
+        // after e4 the start position can't come back
+        play(&mut game, &[((1, 4), (3, 4))]);
+        assert_eq!(game.repetitions(), 0);
+    }
+
+    #[test]
+    fn draws() {
+        let result = |fen: &str| Game::new(Position::from_fen(fen).unwrap()).result();
+        let draw = |termination| {
+            Some(GameResult {
+                winner: None,
+                termination,
+            })
+        };
+        for fen in [
+            "8/8/4k3/8/8/3K4/8/8 w - - 0 1",
+            "8/8/4k3/8/8/3K4/8/6N1 w - - 0 1",
+            "8/8/4k3/8/8/3K4/8/5B2 b - - 0 1",
+            // bishops on light squares only, however many
+            "4b3/8/4k3/8/8/3K4/8/5B1B w - - 0 1",
+        ] {
+            assert_eq!(
+                result(fen),
+                draw(Termination::InsufficientMaterial),
+                "{}",
+                fen
+            );
+        }
+        for fen in [
+            "8/8/4k3/8/8/3K4/8/4BB2 w - - 0 1",
+            "8/8/4k3/8/8/3K4/8/5NN1 w - - 0 1",
+            "8/8/4kn2/8/8/3K4/8/5B2 w - - 0 1",
+            "8/8/4k3/8/8/3K4/4P3/8 w - - 0 1",
+        ] {
+            assert_eq!(result(fen), None, "{}", fen);
+        }
+
+        assert_eq!(
+            result("8/8/4k3/8/8/3K4/8/R7 w - - 150 100"),
+            draw(Termination::SeventyFiveMoveRule)
+        );
+        // mate on the move that reaches the limit still counts
+        assert_eq!(
+            result("R5k1/5ppp/8/8/8/8/8/6K1 b - - 150 100")
+                .unwrap()
+                .termination,
+            Termination::Checkmate
+        );
+    }
+
+    #[test]
+    fn claims_draws() {
+        let mut game = Game::new(Position::from_fen("8/8/4k3/8/8/3K4/8/R7 w - - 99 80").unwrap());
+        assert_eq!(game.claimable_draw(), None);
+        assert_eq!(game.claim_draw(), None);
+        play(&mut game, &[((0, 0), (0, 1))]);
+        assert_eq!(game.claimable_draw(), Some(Termination::FiftyMoveRule));
+
+        let mut game = Game::default();
+        // Nf3 Nf6 Ng1 Ng8
+        let knights = [
+            ((0, 6), (2, 5)),
+            ((7, 6), (5, 5)),
+            ((2, 5), (0, 6)),
+            ((5, 5), (7, 6)),
+        ];
+        play(&mut game, &knights);
+        assert_eq!(game.claimable_draw(), None);
+        play(&mut game, &knights);
+        assert_eq!(
+            game.claimable_draw(),
+            Some(Termination::ThreefoldRepetition)
+        );
+        play(&mut game, &knights);
+        assert_eq!(game.result(), None);
+        assert!(game.claimable_draw().is_some());
+        // nobody claimed it, the fifth time ends the game
+        play(&mut game, &knights[..3]);
+        play(&mut game, &knights[3..]);
+        assert_eq!(
+            game.result().map(|result| result.termination),
+            Some(Termination::FivefoldRepetition)
+        );
+
+        let mut game = Game::default();
+        play(&mut game, &knights);
+        play(&mut game, &knights);
+        assert_eq!(
+            game.claim_draw(),
+            Some(GameResult {
+                winner: None,
+                termination: Termination::ThreefoldRepetition,
+            })
+        );
+        assert_eq!(
+            game.make_move(game.position().legal_moves()[0]),
+            Err(MoveError::GameOver)
+        );
+        // taking back a move takes back the claim with it
+        game.undo();
+        assert_eq!(game.result(), None);
+    }
+
+    #[test]
+    fn resigns() {
+        let mut game = Game::default();
+        play(&mut game, &[((1, 4), (3, 4))]);
+        assert_eq!(
+            game.resign(PieceColor::Black),
+            Some(GameResult {
+                winner: Some(PieceColor::White),
+                termination: Termination::Resignation,
+            })
+        );
+        assert_eq!(game.resign(PieceColor::White), None);
+        assert!(!game.offer_draw(PieceColor::White));
+        assert!(game.legal_moves().is_empty());
+    }
+
+    #[test]
+    fn draw_offers() {
+        let mut game = Game::default();
+        assert_eq!(game.accept_draw(), None);
+        assert!(game.offer_draw(PieceColor::White));
+        assert_eq!(game.draw_offer(), Some(PieceColor::White));
+        assert!(game.decline_draw());
+        assert!(!game.decline_draw());
+
+        // the offer stands while its side moves and lapses once the other side moves
+        game.offer_draw(PieceColor::White);
+        play(&mut game, &[((1, 4), (3, 4))]);
+        assert_eq!(game.draw_offer(), Some(PieceColor::White));
+        play(&mut game, &[((6, 4), (4, 4))]);
+        assert_eq!(game.draw_offer(), None);
+
+        game.offer_draw(PieceColor::White);
+        assert_eq!(
+            game.accept_draw(),
+            Some(GameResult {
+                winner: None,
+                termination: Termination::DrawAgreement,
+            })
+        );
+        assert_eq!(game.draw_offer(), None);
+    }
+
+    #[test]
+    fn flag_falls() {
+        let mut game = Game::default();
+        assert_eq!(
+            game.flag(PieceColor::White),
+            Some(GameResult {
+                winner: Some(PieceColor::Black),
+                termination: Termination::Timeout,
+            })
+        );
+        assert_eq!(game.flag(PieceColor::Black), None);
+
+        let flag = |fen: &str, color| Game::new(Position::from_fen(fen).unwrap()).flag(color);
+        let draw = Some(GameResult {
+            winner: None,
+            termination: Termination::TimeoutVsInsufficientMaterial,
+        });
+        assert_eq!(
+            flag("8/8/4k3/8/8/3K4/8/R7 w - - 0 1", PieceColor::White),
+            draw
+        );
+        // a lone knight can't mate a king with nothing but queens around it, nor a lone bishop
+        // one with only rooks
+        assert_eq!(
+            flag("8/8/4k3/4n3/8/3K4/8/Q7 w - - 0 1", PieceColor::White),
+            draw
+        );
+        assert_eq!(
+            flag("8/8/4k3/4b3/8/3K4/8/R7 w - - 0 1", PieceColor::White),
+            draw
+        );
+        // but a rook or a pawn can be in the way of their own king
+        let black_wins = Some(GameResult {
+            winner: Some(PieceColor::Black),
+            termination: Termination::Timeout,
+        });
+        assert_eq!(
+            flag("8/8/4k3/4n3/8/3K4/8/R7 w - - 0 1", PieceColor::White),
+            black_wins
+        );
+        assert_eq!(
+            flag("8/8/4k3/4b3/8/3K4/P7/8 w - - 0 1", PieceColor::White),
+            black_wins
+        );
+    }
+}
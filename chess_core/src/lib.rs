@@ -0,0 +1,30 @@
+//! Chess rules without any Bevy dependency: board position, move generation and game state.
+
+pub mod bitboard;
+pub mod clock;
+pub mod eval;
+pub mod fen;
+pub mod game;
+pub mod moves;
+pub mod perft;
+pub mod pgn;
+pub mod piece;
+pub mod position;
+pub mod san;
+pub mod search;
+pub mod square;
+pub mod transposition;
+pub mod uci;
+pub mod uci_server;
+pub mod zobrist;
+
+pub use clock::{Bonus, Clock, TimeControl};
+pub use fen::{FenError, START_FEN};
+pub use game::{Game, GameResult, MoveError, Termination};
+pub use moves::{Move, MoveKind};
+pub use pgn::{parse_pgn, PgnError, PgnGame, PgnTags};
+pub use piece::{Piece, PieceColor, PieceType};
+pub use position::{CastlingRights, Position};
+pub use san::SanError;
+pub use search::{Search, SearchInfo, SearchLimits};
+pub use square::Square;
@@ -0,0 +1,110 @@
+use crate::piece::PieceType;
+use crate::position::Position;
+use crate::square::Square;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MoveKind {
+    Normal,
+    DoublePush,
+    EnPassant,
+    CastleKingSide,
+    CastleQueenSide,
+}
+
+/// A move as generated for a position, carrying enough context to be mirrored without the
+/// position at hand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Move {
+    pub from: Square,
+    pub to: Square,
+    pub piece: PieceType,
+    pub captured: Option<PieceType>,
+    pub promotion: Option<PieceType>,
+    pub kind: MoveKind,
+}
+
+impl Move {
+    pub fn is_capture(&self) -> bool {
+        self.captured.is_some()
+    }
+
+    pub fn is_castling(&self) -> bool {
+        matches!(
+            self.kind,
+            MoveKind::CastleKingSide | MoveKind::CastleQueenSide
+        )
+    }
+
+    /// Where the captured piece stood, only differs from `to` for en passant.
+    pub fn captured_square(&self) -> Option<Square> {
+        match self.kind {
+            MoveKind::EnPassant => Some(Square::new(self.from.rank(), self.to.file())),
+            _ if self.is_capture() => Some(self.to),
+            _ => None,
+        }
+    }
+
+    /// The rook's (from, to) squares when castling.
+    pub fn castling_rook(&self) -> Option<(Square, Square)> {
+        let rank = self.from.rank();
+        match self.kind {
+            MoveKind::CastleKingSide => Some((Square::new(rank, 7), Square::new(rank, 5))),
+            MoveKind::CastleQueenSide => Some((Square::new(rank, 0), Square::new(rank, 3))),
+            _ => None,
+        }
+    }
+
+    /// The move in coordinate notation as UCI uses it, `e2e4` or `e7e8q`.
+    pub fn to_uci(&self) -> String {
+        match self.promotion {
+            Some(promotion) => format!(
+                "{}{}{}",
+                self.from,
+                self.to,
+                promotion.to_char().to_ascii_lowercase()
+            ),
+            None => format!("{}{}", self.from, self.to),
+        }
+    }
+
+    /// The legal move in `position` written as `uci`, castling is written as the king's move.
+    pub fn from_uci(position: &Position, uci: &str) -> Option<Move> {
+        if !uci.is_ascii() || !(4..=5).contains(&uci.len()) {
+            return None;
+        }
+        let from = uci[0..2].parse().ok()?;
+        let to = uci[2..4].parse().ok()?;
+        let promotion = match uci[4..].chars().next() {
+            Some(c) => Some(PieceType::from_char(c)?),
+            None => None,
+        };
+        position.legal_move(from, to, promotion)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uci() {
+        let position = Position::from_fen("r3k3/1P6/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        for (uci, kind) in [
+            ("e1g1", Some(MoveKind::CastleKingSide)),
+            ("h1h8", Some(MoveKind::Normal)),
+            ("b7a8n", Some(MoveKind::Normal)),
+            ("b7b8q", Some(MoveKind::Normal)),
+            ("b7b8", None),
+            ("b7b8k", None),
+            ("e1e3", None),
+            ("e1", None),
+            ("x1e2", None),
+        ] {
+            let mv = Move::from_uci(&position, uci);
+            assert_eq!(mv.map(|mv| mv.kind), kind, "{}", uci);
+            if let Some(mv) = mv {
+                assert_eq!(mv.to_uci(), uci);
+            }
+        }
+    }
+}
@@ -0,0 +1,132 @@
+//! Move path enumeration, counting the leaf nodes of the legal move tree to check move generation
+//! against known totals.
+
+use crate::moves::Move;
+use crate::position::Position;
+
+/// Number of move sequences `depth` plies deep from `position`.
+pub fn perft(position: &Position, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let moves = position.legal_moves();
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+    moves
+        .into_iter()
+        .map(|mv| {
+            let mut next = *position;
+            next.make_move(mv);
+            perft(&next, depth - 1)
+        })
+        .sum()
+}
+
+/// `perft` split up by root move, for narrowing down where a count goes wrong.
+pub fn divide(position: &Position, depth: u32) -> Vec<(Move, u64)> {
+    if depth == 0 {
+        return Vec::new();
+    }
+    position
+        .legal_moves()
+        .into_iter()
+        .map(|mv| {
+            let mut next = *position;
+            next.make_move(mv);
+            (mv, perft(&next, depth - 1))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen::START_FEN;
+
+    /// Positions and node counts from https://www.chessprogramming.org/Perft_Results, counts
+    /// start at depth 1.
+    const POSITIONS: [(&str, &[u64]); 6] = [
+        (START_FEN, &[20, 400, 8_902, 197_281, 4_865_609]),
+        (
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            &[48, 2_039, 97_862, 4_085_603],
+        ),
+        (
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            &[14, 191, 2_812, 43_238, 674_624],
+        ),
+        (
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            &[6, 264, 9_467, 422_333],
+        ),
+        (
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            &[44, 1_486, 62_379, 2_103_487],
+        ),
+        (
+            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+            &[46, 2_079, 89_890, 3_894_594],
+        ),
+    ];
+
+    /// Depths with more nodes than this only run with `cargo test -- --ignored`.
+    const QUICK_NODES: u64 = 200_000;
+
+    fn check(index: usize, deep: bool) {
+        let (fen, counts) = POSITIONS[index];
+        let position = Position::from_fen(fen).unwrap();
+        for (depth, &count) in (1..).zip(counts) {
+            if !deep && count > QUICK_NODES {
+                break;
+            }
+            assert_eq!(perft(&position, depth), count, "{} at depth {}", fen, depth);
+        }
+    }
+
+    #[test]
+    fn start_position() {
+        check(0, false);
+    }
+
+    #[test]
+    fn kiwipete() {
+        check(1, false);
+    }
+
+    #[test]
+    fn rook_endgame() {
+        check(2, false);
+    }
+
+    #[test]
+    fn promotions_and_checks() {
+        check(3, false);
+    }
+
+    #[test]
+    fn discovered_checks() {
+        check(4, false);
+    }
+
+    #[test]
+    fn middlegame() {
+        check(5, false);
+    }
+
+    #[test]
+    #[ignore = "millions of nodes, slow in unoptimized builds"]
+    fn all_depths() {
+        for index in 0..POSITIONS.len() {
+            check(index, true);
+        }
+    }
+
+    #[test]
+    fn divide_adds_up() {
+        let position = Position::from_fen(POSITIONS[1].0).unwrap();
+        let divided = divide(&position, 3);
+        assert_eq!(divided.len(), 48);
+        assert_eq!(divided.iter().map(|(_, n)| n).sum::<u64>(), 97_862);
+    }
+}
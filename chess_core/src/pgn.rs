@@ -0,0 +1,709 @@
+//! Portable Game Notation: the Seven Tag Roster followed by SAN movetext.
+
+use crate::fen::{FenError, START_FEN};
+use crate::game::{Game, GameResult, Termination};
+use crate::moves::Move;
+use crate::piece::PieceColor;
+use crate::position::Position;
+use crate::san::SanError;
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Movetext lines are wrapped to at most this many characters.
+const LINE_WIDTH: usize = 80;
+
+/// The Seven Tag Roster apart from `Result`, which comes from the game. Unknown values are
+/// written as `?` the way the PGN standard asks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PgnTags {
+    pub event: String,
+    pub site: String,
+    pub date: String,
+    pub round: String,
+    pub white: String,
+    pub black: String,
+}
+
+impl Default for PgnTags {
+    fn default() -> Self {
+        Self {
+            event: "?".to_string(),
+            site: "?".to_string(),
+            date: "????.??.??".to_string(),
+            round: "?".to_string(),
+            white: "?".to_string(),
+            black: "?".to_string(),
+        }
+    }
+}
+
+/// `YYYY.MM.DD` for the (UTC) day `time` falls on.
+pub fn pgn_date(time: SystemTime) -> String {
+    let days = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs() / 86_400) as i64;
+    // days to civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+/// The PGN result token, `*` while the game is still going.
+pub fn result_token(result: Option<GameResult>) -> &'static str {
+    match result {
+        Some(GameResult {
+            winner: Some(PieceColor::White),
+            ..
+        }) => "1-0",
+        Some(GameResult {
+            winner: Some(PieceColor::Black),
+            ..
+        }) => "0-1",
+        Some(GameResult { winner: None, .. }) => "1/2-1/2",
+        None => "*",
+    }
+}
+
+/// Why the game ended, for results the moves alone don't show.
+fn termination_comment(result: Option<GameResult>) -> Option<String> {
+    let result = result?;
+    match (result.winner, result.termination) {
+        (_, Termination::Checkmate | Termination::Stalemate) => None,
+        (Some(winner), Termination::Resignation) => Some(format!("{} resigns", winner.opposite())),
+        (Some(winner), Termination::Timeout) => {
+            Some(format!("{} loses on time", winner.opposite()))
+        }
+        (_, termination) => Some(format!("Draw by {}", termination)),
+    }
+}
+
+impl Game {
+    /// The game as PGN, games that didn't start from the initial position get `SetUp` and
+    /// `FEN` tags.
+    pub fn to_pgn(&self, tags: &PgnTags) -> String {
+        let result = result_token(self.result());
+        let mut pgn = String::new();
+        for (name, value) in [
+            ("Event", &tags.event),
+            ("Site", &tags.site),
+            ("Date", &tags.date),
+            ("Round", &tags.round),
+            ("White", &tags.white),
+            ("Black", &tags.black),
+        ] {
+            pgn.push_str(&tag(name, value));
+        }
+        pgn.push_str(&tag("Result", result));
+        let fen = self.initial_position().to_fen();
+        if fen != START_FEN {
+            pgn.push_str(&tag("SetUp", "1"));
+            pgn.push_str(&tag("FEN", &fen));
+        }
+        pgn.push('\n');
+
+        let mut tokens = Vec::new();
+        let mut position = self.initial_position();
+        for (ply, &mv) in self.moves().enumerate() {
+            let number = position.fullmove_number();
+            match position.side_to_move() {
+                PieceColor::White => tokens.push(format!("{}.", number)),
+                PieceColor::Black if ply == 0 => tokens.push(format!("{}...", number)),
+                PieceColor::Black => {}
+            }
+            tokens.push(position.san(mv));
+            position.make_move(mv);
+        }
+        if let Some(comment) = termination_comment(self.result()) {
+            tokens.push(format!("{{{}}}", comment));
+        }
+        tokens.push(result.to_string());
+
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > LINE_WIDTH {
+                pgn.push_str(&line);
+                pgn.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        pgn.push_str(&line);
+        pgn.push('\n');
+        pgn
+    }
+}
+
+fn tag(name: &str, value: &str) -> String {
+    let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+    format!("[{} \"{}\"]\n", name, value)
+}
+
+/// A game read from PGN. Only the main line is kept, variations are checked and dropped.
+#[derive(Debug, Clone)]
+pub struct PgnGame {
+    /// Tag pairs in the order they were written.
+    pub tags: Vec<(String, String)>,
+    /// Comment before the first move.
+    pub comment: Option<String>,
+    pub moves: Vec<PgnMove>,
+    /// The result token closing the movetext, `*` when it was missing.
+    pub result: String,
+    initial_position: Position,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PgnMove {
+    pub mv: Move,
+    /// The move as it was written.
+    pub san: String,
+    /// Numeric annotation glyphs, `!` and friends are turned into theirs.
+    pub nags: Vec<u8>,
+    pub comment: Option<String>,
+}
+
+impl PgnGame {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// From the `FEN` tag or the usual start position.
+    pub fn initial_position(&self) -> Position {
+        self.initial_position
+    }
+
+    /// The game after its first `ply` moves, or as far as it got when the moves go on past a
+    /// draw that ends the game by itself.
+    pub fn game_at(&self, ply: usize) -> Game {
+        let mut game = Game::new(self.initial_position);
+        for pgn_move in self.moves.iter().take(ply) {
+            // moves were checked against the position while parsing, so only a finished game
+            // turns one down
+            if game.make_move(pgn_move.mv).is_err() {
+                break;
+            }
+        }
+        game
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PgnError {
+    /// Broken tag, comment or variation brackets.
+    Syntax(String),
+    Fen(FenError),
+    /// A move of game `game` doesn't fit its position, `ply` counts main line moves from 1.
+    Move {
+        game: usize,
+        ply: usize,
+        error: SanError,
+    },
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PgnError::Syntax(err) => write!(f, "PGN syntax error: {}", err),
+            PgnError::Fen(err) => write!(f, "{}", err),
+            PgnError::Move { game, ply, error } => {
+                write!(f, "game {}, ply {}: {}", game, ply, error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+impl From<FenError> for PgnError {
+    fn from(err: FenError) -> Self {
+        PgnError::Fen(err)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Tag(String, String),
+    Comment(String),
+    Nag(u8),
+    VariationStart,
+    VariationEnd,
+    Result(String),
+    San(String),
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, PgnError> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    let mut line_start = true;
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => {
+                line_start = true;
+                continue;
+            }
+            c if c.is_whitespace() => continue,
+            // escaped line, meant for other programs
+            '%' if line_start => {
+                chars.by_ref().take_while(|&c| c != '\n').for_each(drop);
+                continue;
+            }
+            ';' => {
+                let comment = chars
+                    .by_ref()
+                    .take_while(|&c| c != '\n')
+                    .collect::<String>();
+                tokens.push(Token::Comment(comment.trim().to_string()));
+                line_start = true;
+                continue;
+            }
+            '{' => {
+                let mut comment = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => comment.push(c),
+                        None => return Err(PgnError::Syntax("unterminated comment".to_string())),
+                    }
+                }
+                tokens.push(Token::Comment(comment.trim().to_string()));
+            }
+            '[' => tokens.push(tag_pair(&mut chars)?),
+            '(' => tokens.push(Token::VariationStart),
+            ')' => tokens.push(Token::VariationEnd),
+            '$' => {
+                let mut digits = String::new();
+                while let Some(&c) = chars.peek().filter(|c| c.is_ascii_digit()) {
+                    digits.push(c);
+                    chars.next();
+                }
+                let nag = digits
+                    .parse()
+                    .map_err(|_| PgnError::Syntax(format!("bad NAG ${}", digits)))?;
+                tokens.push(Token::Nag(nag));
+            }
+            _ => {
+                let mut symbol = c.to_string();
+                while let Some(&c) = chars
+                    .peek()
+                    .filter(|c| !c.is_whitespace() && !"{}()[];$".contains(**c))
+                {
+                    symbol.push(c);
+                    chars.next();
+                }
+                symbol_tokens(&symbol, &mut tokens);
+            }
+        }
+        line_start = false;
+    }
+    Ok(tokens)
+}
+
+fn tag_pair(chars: &mut impl Iterator<Item = char>) -> Result<Token, PgnError> {
+    let mut name = String::new();
+    let mut value = String::new();
+    let mut chars = chars.skip_while(|c| c.is_whitespace());
+    for c in chars.by_ref() {
+        if c == '"' {
+            break;
+        }
+        name.push(c);
+    }
+    loop {
+        match chars.next() {
+            Some('\\') => value.extend(chars.next()),
+            Some('"') => break,
+            Some(c) => value.push(c),
+            None => return Err(PgnError::Syntax("unterminated tag".to_string())),
+        }
+    }
+    if chars.find(|c| !c.is_whitespace()) != Some(']') {
+        return Err(PgnError::Syntax(format!(
+            "unterminated tag {}",
+            name.trim()
+        )));
+    }
+    Ok(Token::Tag(name.trim().to_string(), value))
+}
+
+/// Splits a run of symbol characters into result, move number, SAN and suffix annotations.
+fn symbol_tokens(symbol: &str, tokens: &mut Vec<Token>) {
+    if matches!(symbol, "1-0" | "0-1" | "1/2-1/2" | "*") {
+        tokens.push(Token::Result(symbol.to_string()));
+        return;
+    }
+    // move numbers like `12.` or `12...`, possibly glued to the move
+    let symbol = match symbol.find(|c: char| !c.is_ascii_digit()) {
+        Some(end) if end > 0 && symbol[end..].starts_with('.') => {
+            symbol[end..].trim_start_matches('.')
+        }
+        _ => symbol,
+    };
+    let annotation_start = symbol
+        .find(|c| c == '!' || c == '?')
+        .unwrap_or(symbol.len());
+    let (san, annotation) = symbol.split_at(annotation_start);
+    if !san.is_empty() {
+        tokens.push(Token::San(san.to_string()));
+    }
+    let nag = match annotation {
+        "!" => 1,
+        "?" => 2,
+        "!!" => 3,
+        "??" => 4,
+        "!?" => 5,
+        "?!" => 6,
+        _ => return,
+    };
+    tokens.push(Token::Nag(nag));
+}
+
+/// Where a line of moves stands: its current position and the one before its last move, which
+/// is where a variation on that move starts.
+#[derive(Clone, Copy)]
+struct Line {
+    position: Position,
+    before_last: Option<Position>,
+}
+
+struct GameBuilder {
+    game: PgnGame,
+    main: Line,
+    variations: Vec<Line>,
+    movetext_started: bool,
+}
+
+impl GameBuilder {
+    fn new() -> Self {
+        let position = Position::start();
+        Self {
+            game: PgnGame {
+                tags: Vec::new(),
+                comment: None,
+                moves: Vec::new(),
+                result: "*".to_string(),
+                initial_position: position,
+            },
+            main: Line {
+                position,
+                before_last: None,
+            },
+            variations: Vec::new(),
+            movetext_started: false,
+        }
+    }
+
+    fn start_movetext(&mut self) -> Result<(), PgnError> {
+        if !self.movetext_started {
+            self.movetext_started = true;
+            if let Some(fen) = self.game.tag("FEN") {
+                let position = Position::from_fen(fen)?;
+                self.game.initial_position = position;
+                self.main.position = position;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<PgnGame, PgnError> {
+        if !self.variations.is_empty() {
+            return Err(PgnError::Syntax("unclosed variation".to_string()));
+        }
+        Ok(self.game)
+    }
+}
+
+/// Reads every game in `text`, checking each move against the rules.
+pub fn parse_pgn(text: &str) -> Result<Vec<PgnGame>, PgnError> {
+    let mut games = Vec::new();
+    let mut builder: Option<GameBuilder> = None;
+    for token in tokenize(text)? {
+        // tags after movetext belong to the next game
+        if matches!(token, Token::Tag(..)) && builder.as_ref().map_or(false, |b| b.movetext_started)
+        {
+            games.push(builder.take().unwrap().finish()?);
+        }
+        let current = builder.get_or_insert_with(GameBuilder::new);
+        if matches!(
+            token,
+            Token::San(_) | Token::VariationStart | Token::Result(_)
+        ) {
+            current.start_movetext()?;
+        }
+
+        match token {
+            Token::Tag(name, value) => current.game.tags.push((name, value)),
+            Token::San(san) => {
+                let in_main_line = current.variations.is_empty();
+                // variations report the main line move they branch off
+                let ply = current.game.moves.len() + usize::from(in_main_line);
+                let line = current.variations.last_mut().unwrap_or(&mut current.main);
+                let mv = line
+                    .position
+                    .parse_san(&san)
+                    .map_err(|error| PgnError::Move {
+                        game: games.len() + 1,
+                        ply,
+                        error,
+                    })?;
+                line.before_last = Some(line.position);
+                line.position.make_move(mv);
+                if in_main_line {
+                    current.game.moves.push(PgnMove {
+                        mv,
+                        san,
+                        nags: Vec::new(),
+                        comment: None,
+                    });
+                }
+            }
+            Token::Comment(comment) if current.variations.is_empty() => {
+                match current.game.moves.last_mut() {
+                    Some(last) => last.comment = Some(comment),
+                    None => current.game.comment = Some(comment),
+                }
+            }
+            Token::Nag(nag) if current.variations.is_empty() => {
+                if let Some(last) = current.game.moves.last_mut() {
+                    last.nags.push(nag);
+                }
+            }
+            Token::Comment(_) | Token::Nag(_) => {}
+            Token::VariationStart => {
+                let line = current.variations.last().unwrap_or(&current.main);
+                let position = line
+                    .before_last
+                    .ok_or_else(|| PgnError::Syntax("variation before any move".to_string()))?;
+                current.variations.push(Line {
+                    position,
+                    before_last: None,
+                });
+            }
+            Token::VariationEnd => {
+                if current.variations.pop().is_none() {
+                    return Err(PgnError::Syntax("unopened variation".to_string()));
+                }
+            }
+            Token::Result(result) => {
+                let mut finished = builder.take().unwrap();
+                finished.game.result = result;
+                games.push(finished.finish()?);
+            }
+        }
+    }
+    if let Some(builder) = builder.filter(|b| b.movetext_started || !b.game.tags.is_empty()) {
+        games.push(builder.finish()?);
+    }
+    Ok(games)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::Position;
+    use crate::square::Square;
+    use std::time::Duration;
+
+    fn play(game: &mut Game, moves: &[(&str, &str)]) {
+        for &(from, to) in moves {
+            let from = from.parse::<Square>().unwrap();
+            let to = to.parse::<Square>().unwrap();
+            let mv = game.position().legal_move(from, to, None).unwrap();
+            game.make_move(mv).unwrap();
+        }
+    }
+
+    #[test]
+    fn finished_game() {
+        let mut game = Game::default();
+        play(
+            &mut game,
+            &[("f2", "f3"), ("e7", "e5"), ("g2", "g4"), ("d8", "h4")],
+        );
+        let tags = PgnTags {
+            white: "Fool".to_string(),
+            ..PgnTags::default()
+        };
+        assert_eq!(
+            game.to_pgn(&tags),
+            "[Event \"?\"]\n\
+             [Site \"?\"]\n\
+             [Date \"????.??.??\"]\n\
+             [Round \"?\"]\n\
+             [White \"Fool\"]\n\
+             [Black \"?\"]\n\
+             [Result \"0-1\"]\n\
+             \n\
+             1. f3 e5 2. g4 Qh4# 0-1\n"
+        );
+    }
+
+    #[test]
+    fn records_how_games_end() {
+        let mut game = Game::default();
+        play(&mut game, &[("e2", "e4")]);
+        game.resign(PieceColor::Black);
+        let pgn = game.to_pgn(&PgnTags::default());
+        assert!(pgn.ends_with("\n\n1. e4 {Black resigns} 1-0\n"));
+        let games = parse_pgn(&pgn).unwrap();
+        assert_eq!(games[0].moves[0].comment.as_deref(), Some("Black resigns"));
+
+        let mut game = Game::default();
+        game.offer_draw(PieceColor::White);
+        game.accept_draw();
+        let pgn = game.to_pgn(&PgnTags::default());
+        assert!(pgn.ends_with("\n\n{Draw by agreement} 1/2-1/2\n"));
+    }
+
+    #[test]
+    fn game_in_progress_from_fen() {
+        let position = Position::from_fen("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 3 20").unwrap();
+        let mut game = Game::new(position);
+        play(&mut game, &[("e8", "c8"), ("a1", "a8")]);
+        let pgn = game.to_pgn(&PgnTags::default());
+        assert!(pgn.contains("[Result \"*\"]\n[SetUp \"1\"]\n"));
+        assert!(pgn.contains("[FEN \"r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 3 20\"]\n"));
+        assert!(pgn.ends_with("\n\n20... O-O-O 21. Ra8+ *\n"));
+    }
+
+    #[test]
+    fn long_games_wrap() {
+        let mut game = Game::default();
+        let knights = [("g1", "f3"), ("g8", "f6"), ("f3", "g1"), ("f6", "g8")];
+        // a pawn move each time round so the game isn't drawn by repetition
+        for (white, black) in [("a2", "a3"), ("b2", "b3"), ("c2", "c3"), ("d2", "d3")]
+            .into_iter()
+            .zip([("a7", "a6"), ("b7", "b6"), ("c7", "c6"), ("d7", "d6")])
+        {
+            play(&mut game, &knights);
+            play(&mut game, &[white, black]);
+        }
+        let pgn = game.to_pgn(&PgnTags::default());
+        let movetext = pgn.split("\n\n").nth(1).unwrap();
+        assert!(movetext.lines().count() > 1);
+        assert!(movetext.lines().all(|line| line.len() <= LINE_WIDTH));
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(pgn_date(UNIX_EPOCH), "1970.01.01");
+        let leap_day = UNIX_EPOCH + Duration::from_secs(951_782_400);
+        assert_eq!(pgn_date(leap_day), "2000.02.29");
+    }
+
+    const GAMES: &str = r#"[Event "Casual"]
+[Site "?"]
+[White "Anderssen"]
+[Black "Kieseritzky"]
+[Result "1-0"]
+
+{ The Immortal Game, opening only }
+1. e4 e5 2. f4 $2 exf4 3. Bc4 Qh4+ (3... d5 4. Bxd5 Nf6) 4. Kf1 b5?! ; speculative
+5. Bxb5 Nf6 1-0
+
+[Event "Setup"]
+[SetUp "1"]
+[FEN "4k3/P7/8/8/8/8/8/4K3 w - - 0 60"]
+
+60. a8=Q+ Kd7 (60... Ke7 61. Qb7+) 61.Qb7+ *
+"#;
+
+    #[test]
+    fn parses_games() {
+        let games = parse_pgn(GAMES).unwrap();
+        assert_eq!(games.len(), 2);
+
+        let immortal = &games[0];
+        assert_eq!(immortal.tag("White"), Some("Anderssen"));
+        assert_eq!(
+            immortal.comment.as_deref(),
+            Some("The Immortal Game, opening only")
+        );
+        assert_eq!(immortal.result, "1-0");
+        let sans = immortal
+            .moves
+            .iter()
+            .map(|m| m.san.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(
+            sans,
+            ["e4", "e5", "f4", "exf4", "Bc4", "Qh4+", "Kf1", "b5", "Bxb5", "Nf6"]
+        );
+        assert_eq!(immortal.moves[2].nags, [2]);
+        assert_eq!(immortal.moves[7].nags, [6]);
+        assert_eq!(immortal.moves[7].comment.as_deref(), Some("speculative"));
+        assert_eq!(
+            immortal.game_at(10).position().to_fen(),
+            "rnb1kb1r/p1pp1ppp/5n2/1B6/4Pp1q/8/PPPP2PP/RNBQ1KNR w kq - 1 6"
+        );
+
+        let setup = &games[1];
+        assert_eq!(setup.result, "*");
+        assert_eq!(setup.moves.len(), 3);
+        assert_eq!(
+            setup.initial_position(),
+            Position::from_fen("4k3/P7/8/8/8/8/8/4K3 w - - 0 60").unwrap()
+        );
+    }
+
+    #[test]
+    fn exported_games_read_back() {
+        let mut game = Game::default();
+        play(
+            &mut game,
+            &[("e2", "e4"), ("d7", "d5"), ("e4", "d5"), ("d8", "d5")],
+        );
+        let games = parse_pgn(&game.to_pgn(&PgnTags::default())).unwrap();
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].game_at(4).position(), game.position());
+    }
+
+    #[test]
+    fn moves_past_an_automatic_draw() {
+        let games =
+            parse_pgn("[SetUp \"1\"]\n[FEN \"8/8/4k3/8/8/3K4/8/5B2 w - - 0 1\"]\n\n1. Kd4 Kd6 *")
+                .unwrap();
+        let game = games[0].game_at(2);
+        assert_eq!(game.moves().count(), 0);
+        assert_eq!(
+            game.result().map(|result| result.termination),
+            Some(Termination::InsufficientMaterial)
+        );
+    }
+
+    #[test]
+    fn rejects_broken_games() {
+        assert_eq!(
+            parse_pgn("1. e4 e5 2. Ke3").unwrap_err(),
+            PgnError::Move {
+                game: 1,
+                ply: 3,
+                error: SanError::Illegal("Ke3".to_string()),
+            }
+        );
+        assert!(matches!(
+            parse_pgn("1. e4 { unterminated"),
+            Err(PgnError::Syntax(_))
+        ));
+        assert!(matches!(
+            parse_pgn("1. e4 (1. d4 *"),
+            Err(PgnError::Syntax(_))
+        ));
+        assert!(matches!(
+            parse_pgn("[FEN \"8/8 w - -\"]\n1. e4 *"),
+            Err(PgnError::Fen(_))
+        ));
+    }
+}
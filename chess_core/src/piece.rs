@@ -0,0 +1,108 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PieceType {
+    Pawn,
+    Rook,
+    Knight,
+    Bishop,
+    Queen,
+    King,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PieceColor {
+    White,
+    Black,
+}
+
+impl PieceType {
+    pub const ALL: [PieceType; 6] = [
+        PieceType::Pawn,
+        PieceType::Rook,
+        PieceType::Knight,
+        PieceType::Bishop,
+        PieceType::Queen,
+        PieceType::King,
+    ];
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Upper case letter used for the piece in FEN and algebraic notation.
+    pub fn to_char(self) -> char {
+        match self {
+            PieceType::Pawn => 'P',
+            PieceType::Rook => 'R',
+            PieceType::Knight => 'N',
+            PieceType::Bishop => 'B',
+            PieceType::Queen => 'Q',
+            PieceType::King => 'K',
+        }
+    }
+
+    /// Reads a piece letter in either case.
+    pub fn from_char(c: char) -> Option<Self> {
+        match c.to_ascii_uppercase() {
+            'P' => Some(PieceType::Pawn),
+            'R' => Some(PieceType::Rook),
+            'N' => Some(PieceType::Knight),
+            'B' => Some(PieceType::Bishop),
+            'Q' => Some(PieceType::Queen),
+            'K' => Some(PieceType::King),
+            _ => None,
+        }
+    }
+}
+
+impl PieceColor {
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    pub fn opposite(self) -> Self {
+        match self {
+            PieceColor::White => PieceColor::Black,
+            PieceColor::Black => PieceColor::White,
+        }
+    }
+}
+
+impl fmt::Display for PieceColor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PieceColor::White => "White",
+            PieceColor::Black => "Black",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Piece {
+    pub piece_type: PieceType,
+    pub color: PieceColor,
+}
+
+impl Piece {
+    pub fn new(piece_type: PieceType, color: PieceColor) -> Self {
+        Self { piece_type, color }
+    }
+
+    /// FEN letter of the piece, upper case for white and lower case for black.
+    pub fn to_char(self) -> char {
+        match self.color {
+            PieceColor::White => self.piece_type.to_char(),
+            PieceColor::Black => self.piece_type.to_char().to_ascii_lowercase(),
+        }
+    }
+
+    pub fn from_char(c: char) -> Option<Self> {
+        let color = if c.is_ascii_uppercase() {
+            PieceColor::White
+        } else {
+            PieceColor::Black
+        };
+        PieceType::from_char(c).map(|piece_type| Self::new(piece_type, color))
+    }
+}
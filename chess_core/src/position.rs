@@ -0,0 +1,866 @@
+use crate::bitboard::{self, Bitboard, EMPTY};
+use crate::moves::{Move, MoveKind};
+use crate::piece::{Piece, PieceColor, PieceType};
+use crate::square::Square;
+use crate::zobrist;
+
+const PROMOTIONS: [PieceType; 4] = [
+    PieceType::Queen,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+];
+
+/// Which sides each player may still castle to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CastlingRights {
+    pub white_king_side: bool,
+    pub white_queen_side: bool,
+    pub black_king_side: bool,
+    pub black_queen_side: bool,
+}
+
+impl Default for CastlingRights {
+    fn default() -> Self {
+        Self {
+            white_king_side: true,
+            white_queen_side: true,
+            black_king_side: true,
+            black_queen_side: true,
+        }
+    }
+}
+
+impl CastlingRights {
+    pub const NONE: CastlingRights = CastlingRights {
+        white_king_side: false,
+        white_queen_side: false,
+        black_king_side: false,
+        black_queen_side: false,
+    };
+
+    /// The four rights as the low bits of a number, for indexing tables.
+    pub(crate) fn bits(&self) -> usize {
+        usize::from(self.white_king_side)
+            | usize::from(self.white_queen_side) << 1
+            | usize::from(self.black_king_side) << 2
+            | usize::from(self.black_queen_side) << 3
+    }
+
+    /// (king side, queen side) rights for `color`.
+    pub fn for_color(&self, color: PieceColor) -> (bool, bool) {
+        match color {
+            PieceColor::White => (self.white_king_side, self.white_queen_side),
+            PieceColor::Black => (self.black_king_side, self.black_queen_side),
+        }
+    }
+
+    /// Drops the rights lost by moving `piece` from `from` to `to`: any king move loses both
+    /// sides, and moving from or capturing on a rook's home corner loses that side.
+    fn update(&mut self, piece: Piece, from: Square, to: Square) {
+        if piece.piece_type == PieceType::King {
+            match piece.color {
+                PieceColor::White => {
+                    self.white_king_side = false;
+                    self.white_queen_side = false;
+                }
+                PieceColor::Black => {
+                    self.black_king_side = false;
+                    self.black_queen_side = false;
+                }
+            }
+        }
+        for square in [from, to] {
+            match (square.rank(), square.file()) {
+                (0, 0) => self.white_queen_side = false,
+                (0, 7) => self.white_king_side = false,
+                (7, 0) => self.black_queen_side = false,
+                (7, 7) => self.black_king_side = false,
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Piece placement plus everything else that decides which moves are legal.
+///
+/// Placement is kept as one bitboard per piece type and one per color, a piece type's board
+/// `&` a color's board gives the squares of that exact piece.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pieces: [Bitboard; 6],
+    colors: [Bitboard; 2],
+    side_to_move: PieceColor,
+    castling: CastlingRights,
+    /// Square skipped by the last double pawn push, where it can be taken en passant.
+    en_passant: Option<Square>,
+    /// Moves since the last capture or pawn move, for the fifty-move rule.
+    halfmove_clock: u32,
+    /// Starts at 1 and goes up after every black move.
+    fullmove_number: u32,
+    /// Zobrist hash of everything but the en passant square, see `hash`.
+    key: u64,
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Self::start()
+    }
+}
+
+impl Position {
+    /// A board without any pieces, white to move and nobody allowed to castle.
+    pub fn empty() -> Self {
+        Self {
+            pieces: [EMPTY; 6],
+            colors: [EMPTY; 2],
+            side_to_move: PieceColor::White,
+            castling: CastlingRights::NONE,
+            en_passant: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            key: zobrist::castling(CastlingRights::NONE.bits()),
+        }
+    }
+
+    pub fn start() -> Self {
+        let mut position = Self::empty();
+        let back_rank = [
+            PieceType::Rook,
+            PieceType::Knight,
+            PieceType::Bishop,
+            PieceType::Queen,
+            PieceType::King,
+            PieceType::Bishop,
+            PieceType::Knight,
+            PieceType::Rook,
+        ];
+        for (file, piece_type) in back_rank.into_iter().enumerate() {
+            let file = file as u8;
+            position.put(
+                Square::new(0, file),
+                Piece::new(piece_type, PieceColor::White),
+            );
+            position.put(
+                Square::new(1, file),
+                Piece::new(PieceType::Pawn, PieceColor::White),
+            );
+            position.put(
+                Square::new(6, file),
+                Piece::new(PieceType::Pawn, PieceColor::Black),
+            );
+            position.put(
+                Square::new(7, file),
+                Piece::new(piece_type, PieceColor::Black),
+            );
+        }
+        position.set_castling(CastlingRights::default());
+        position
+    }
+
+    pub fn piece_at(&self, square: Square) -> Option<Piece> {
+        let bit = bitboard::bit(square);
+        let color = if self.colors[PieceColor::White.index()] & bit != EMPTY {
+            PieceColor::White
+        } else if self.colors[PieceColor::Black.index()] & bit != EMPTY {
+            PieceColor::Black
+        } else {
+            return None;
+        };
+        PieceType::ALL
+            .into_iter()
+            .find(|piece_type| self.pieces[piece_type.index()] & bit != EMPTY)
+            .map(|piece_type| Piece::new(piece_type, color))
+    }
+
+    /// Puts `piece` on `square`, replacing whatever stood there.
+    pub fn put(&mut self, square: Square, piece: Piece) {
+        self.remove(square);
+        let bit = bitboard::bit(square);
+        self.pieces[piece.piece_type.index()] |= bit;
+        self.colors[piece.color.index()] |= bit;
+        self.key ^= zobrist::piece(piece, square);
+    }
+
+    pub fn remove(&mut self, square: Square) -> Option<Piece> {
+        let piece = self.piece_at(square)?;
+        let bit = bitboard::bit(square);
+        self.pieces[piece.piece_type.index()] &= !bit;
+        self.colors[piece.color.index()] &= !bit;
+        self.key ^= zobrist::piece(piece, square);
+        Some(piece)
+    }
+
+    /// Every occupied square with the piece standing on it.
+    pub fn pieces(&self) -> impl Iterator<Item = (Square, Piece)> + '_ {
+        bitboard::squares(self.occupied())
+            .filter_map(move |square| self.piece_at(square).map(|p| (square, p)))
+    }
+
+    /// The squares holding `piece_type` pieces of `color`.
+    pub fn bitboard(&self, piece_type: PieceType, color: PieceColor) -> Bitboard {
+        self.pieces[piece_type.index()] & self.colors[color.index()]
+    }
+
+    /// The squares holding any piece of `color`.
+    pub fn color_bitboard(&self, color: PieceColor) -> Bitboard {
+        self.colors[color.index()]
+    }
+
+    pub fn occupied(&self) -> Bitboard {
+        self.colors[0] | self.colors[1]
+    }
+
+    pub fn side_to_move(&self) -> PieceColor {
+        self.side_to_move
+    }
+
+    pub fn set_side_to_move(&mut self, color: PieceColor) {
+        self.key ^= zobrist::side_to_move(self.side_to_move) ^ zobrist::side_to_move(color);
+        self.side_to_move = color;
+    }
+
+    pub fn castling(&self) -> CastlingRights {
+        self.castling
+    }
+
+    pub fn set_castling(&mut self, castling: CastlingRights) {
+        self.key ^= zobrist::castling(self.castling.bits()) ^ zobrist::castling(castling.bits());
+        self.castling = castling;
+    }
+
+    pub fn en_passant(&self) -> Option<Square> {
+        self.en_passant
+    }
+
+    pub fn set_en_passant(&mut self, square: Option<Square>) {
+        self.en_passant = square;
+    }
+
+    /// The en passant square when the side to move has a pawn that can take there.
+    pub(crate) fn en_passant_capture(&self) -> Option<Square> {
+        self.en_passant.filter(|&square| {
+            let color = self.side_to_move;
+            bitboard::pawn_attacks(color.opposite(), square) & self.bitboard(PieceType::Pawn, color)
+                != EMPTY
+        })
+    }
+
+    /// Zobrist hash identifying the position for repetitions and the transposition table. The
+    /// move counters are left out and an en passant square only counts when it can be used.
+    pub fn hash(&self) -> u64 {
+        match self.en_passant_capture() {
+            Some(square) => self.key ^ zobrist::en_passant(square),
+            None => self.key,
+        }
+    }
+
+    pub fn halfmove_clock(&self) -> u32 {
+        self.halfmove_clock
+    }
+
+    pub fn set_halfmove_clock(&mut self, halfmove_clock: u32) {
+        self.halfmove_clock = halfmove_clock;
+    }
+
+    pub fn fullmove_number(&self) -> u32 {
+        self.fullmove_number
+    }
+
+    pub fn set_fullmove_number(&mut self, fullmove_number: u32) {
+        self.fullmove_number = fullmove_number;
+    }
+
+    pub fn king_square(&self, color: PieceColor) -> Option<Square> {
+        bitboard::squares(self.bitboard(PieceType::King, color)).next()
+    }
+
+    /// Whether the king of `color` is attacked.
+    pub fn is_in_check(&self, color: PieceColor) -> bool {
+        match self.king_square(color) {
+            Some(king) => self.is_attacked(king, color.opposite()),
+            None => false,
+        }
+    }
+
+    /// Whether any piece of `by` attacks `square`, regardless of what stands on it.
+    pub fn is_attacked(&self, square: Square, by: PieceColor) -> bool {
+        let occupied = self.occupied();
+        let queens = self.bitboard(PieceType::Queen, by);
+        // a pawn of ours on the square would capture exactly where their pawns attack from
+        bitboard::pawn_attacks(by.opposite(), square) & self.bitboard(PieceType::Pawn, by) != EMPTY
+            || bitboard::knight_attacks(square) & self.bitboard(PieceType::Knight, by) != EMPTY
+            || bitboard::king_attacks(square) & self.bitboard(PieceType::King, by) != EMPTY
+            || bitboard::bishop_attacks(square, occupied)
+                & (self.bitboard(PieceType::Bishop, by) | queens)
+                != EMPTY
+            || bitboard::rook_attacks(square, occupied)
+                & (self.bitboard(PieceType::Rook, by) | queens)
+                != EMPTY
+    }
+
+    /// Whether neither side can ever mate: only kings and knights or bishops are left, and
+    /// either a single minor piece or only bishops all on squares of one color.
+    pub fn has_insufficient_material(&self) -> bool {
+        let heavy = self.pieces[PieceType::Pawn.index()]
+            | self.pieces[PieceType::Rook.index()]
+            | self.pieces[PieceType::Queen.index()];
+        if heavy != EMPTY {
+            return false;
+        }
+        let knights = self.pieces[PieceType::Knight.index()];
+        let bishops = self.pieces[PieceType::Bishop.index()];
+        (knights | bishops).count_ones() <= 1
+            || knights == EMPTY
+                && (bishops & bitboard::LIGHT_SQUARES == EMPTY
+                    || bishops & !bitboard::LIGHT_SQUARES == EMPTY)
+    }
+
+    /// Whether `color` could still mate by some series of legal moves. A lone knight needs the
+    /// other king hemmed in by anything but queens, bishops all on one color need a knight, pawn
+    /// or bishop of the other color in the way, anything more than that can mate.
+    pub fn can_mate(&self, color: PieceColor) -> bool {
+        let heavy = self.bitboard(PieceType::Pawn, color)
+            | self.bitboard(PieceType::Rook, color)
+            | self.bitboard(PieceType::Queen, color);
+        if heavy != EMPTY {
+            return true;
+        }
+        let theirs = |piece_type| self.bitboard(piece_type, color.opposite());
+        let knights = self.bitboard(PieceType::Knight, color);
+        let bishops = self.bitboard(PieceType::Bishop, color);
+        let light = bishops & bitboard::LIGHT_SQUARES;
+        let dark = bishops & !bitboard::LIGHT_SQUARES;
+        match (knights.count_ones(), light != EMPTY, dark != EMPTY) {
+            (0, false, false) => false,
+            (0, true, false) | (0, false, true) => {
+                let other_color = if light != EMPTY {
+                    !bitboard::LIGHT_SQUARES
+                } else {
+                    bitboard::LIGHT_SQUARES
+                };
+                theirs(PieceType::Knight) | theirs(PieceType::Pawn) != EMPTY
+                    || theirs(PieceType::Bishop) & other_color != EMPTY
+            }
+            (1, false, false) => {
+                theirs(PieceType::Pawn)
+                    | theirs(PieceType::Knight)
+                    | theirs(PieceType::Bishop)
+                    | theirs(PieceType::Rook)
+                    != EMPTY
+            }
+            _ => true,
+        }
+    }
+
+    /// Every move for the side to move that doesn't leave its own king attacked.
+    pub fn legal_moves(&self) -> Vec<Move> {
+        let color = self.side_to_move;
+        self.pseudo_moves()
+            .into_iter()
+            .filter(|&mv| {
+                let mut next = *self;
+                next.make_move(mv);
+                !next.is_in_check(color)
+            })
+            .collect()
+    }
+
+    /// Finds the legal move between two squares, `promotion` picks among promotion moves.
+    pub fn legal_move(
+        &self,
+        from: Square,
+        to: Square,
+        promotion: Option<PieceType>,
+    ) -> Option<Move> {
+        self.legal_moves()
+            .into_iter()
+            .find(|mv| mv.from == from && mv.to == to && mv.promotion == promotion)
+    }
+
+    /// Moves for the side to move following how the pieces move, ignoring whether the own king
+    /// ends up in check. Castling is only generated when not castling out of or through check.
+    pub fn pseudo_moves(&self) -> Vec<Move> {
+        let mut moves = Vec::with_capacity(64);
+        let color = self.side_to_move;
+        let occupied = self.occupied();
+        let targets = !self.color_bitboard(color);
+
+        self.pawn_moves(color, &mut moves);
+        for piece_type in [
+            PieceType::Knight,
+            PieceType::Bishop,
+            PieceType::Rook,
+            PieceType::Queen,
+            PieceType::King,
+        ] {
+            for from in bitboard::squares(self.bitboard(piece_type, color)) {
+                let attacks = match piece_type {
+                    PieceType::Knight => bitboard::knight_attacks(from),
+                    PieceType::Bishop => bitboard::bishop_attacks(from, occupied),
+                    PieceType::Rook => bitboard::rook_attacks(from, occupied),
+                    PieceType::Queen => bitboard::queen_attacks(from, occupied),
+                    _ => bitboard::king_attacks(from),
+                };
+                for to in bitboard::squares(attacks & targets) {
+                    moves.push(Move {
+                        from,
+                        to,
+                        piece: piece_type,
+                        captured: self.piece_at(to).map(|p| p.piece_type),
+                        promotion: None,
+                        kind: MoveKind::Normal,
+                    });
+                }
+            }
+        }
+        if let Some(king) = self.king_square(color) {
+            self.castling_moves(king, color, &mut moves);
+        }
+        moves
+    }
+
+    fn pawn_moves(&self, color: PieceColor, moves: &mut Vec<Move>) {
+        let (direction, start_rank, last_rank) = match color {
+            PieceColor::White => (1, 1, 7),
+            PieceColor::Black => (-1, 6, 0),
+        };
+        let empty = !self.occupied();
+        let enemies = self.color_bitboard(color.opposite());
+        let en_passant = self.en_passant.map_or(EMPTY, bitboard::bit);
+
+        for from in bitboard::squares(self.bitboard(PieceType::Pawn, color)) {
+            let mut add = |to: Square, captured: Option<PieceType>, kind: MoveKind| {
+                let mut push = |promotion: Option<PieceType>| {
+                    moves.push(Move {
+                        from,
+                        to,
+                        piece: PieceType::Pawn,
+                        captured,
+                        promotion,
+                        kind,
+                    })
+                };
+                if to.rank() == last_rank {
+                    for piece_type in PROMOTIONS {
+                        push(Some(piece_type));
+                    }
+                } else {
+                    push(None);
+                }
+            };
+
+            // pushes only ever go onto empty squares
+            if let Some(forward) = from.offset(direction, 0) {
+                if bitboard::bit(forward) & empty != EMPTY {
+                    add(forward, None, MoveKind::Normal);
+                    if from.rank() == start_rank {
+                        if let Some(double) = forward.offset(direction, 0) {
+                            if bitboard::bit(double) & empty != EMPTY {
+                                add(double, None, MoveKind::DoublePush);
+                            }
+                        }
+                    }
+                }
+            }
+
+            // and captures only diagonally
+            let attacks = bitboard::pawn_attacks(color, from);
+            for to in bitboard::squares(attacks & enemies) {
+                add(
+                    to,
+                    self.piece_at(to).map(|p| p.piece_type),
+                    MoveKind::Normal,
+                );
+            }
+            for to in bitboard::squares(attacks & en_passant) {
+                add(to, Some(PieceType::Pawn), MoveKind::EnPassant);
+            }
+        }
+    }
+
+    /// The two-square king moves, the king may not castle out of, through or into check.
+    fn castling_moves(&self, from: Square, color: PieceColor, moves: &mut Vec<Move>) {
+        let rank = match color {
+            PieceColor::White => 0,
+            PieceColor::Black => 7,
+        };
+        if from != Square::new(rank, 4) {
+            return;
+        }
+
+        let (king_side, queen_side) = self.castling.for_color(color);
+        let has_rook = |file: u8| {
+            self.bitboard(PieceType::Rook, color) & bitboard::bit(Square::new(rank, file)) != EMPTY
+        };
+        let is_empty = |files: &[u8]| {
+            files
+                .iter()
+                .all(|&file| self.occupied() & bitboard::bit(Square::new(rank, file)) == EMPTY)
+        };
+        let is_safe = |files: &[u8]| {
+            files
+                .iter()
+                .all(|&file| !self.is_attacked(Square::new(rank, file), color.opposite()))
+        };
+
+        let mut add = |file: u8, kind: MoveKind| {
+            moves.push(Move {
+                from,
+                to: Square::new(rank, file),
+                piece: PieceType::King,
+                captured: None,
+                promotion: None,
+                kind,
+            });
+        };
+        if king_side && has_rook(7) && is_empty(&[5, 6]) && is_safe(&[4, 5, 6]) {
+            add(6, MoveKind::CastleKingSide);
+        }
+        if queen_side && has_rook(0) && is_empty(&[1, 2, 3]) && is_safe(&[4, 3, 2]) {
+            add(2, MoveKind::CastleQueenSide);
+        }
+    }
+
+    /// Plays `mv` on the board and passes the turn, `mv` is expected to come from this position.
+    pub fn make_move(&mut self, mv: Move) {
+        let piece = self
+            .remove(mv.from)
+            .expect("no piece on the square being moved from");
+
+        if mv.kind == MoveKind::EnPassant {
+            if let Some(captured) = mv.captured_square() {
+                self.remove(captured);
+            }
+        }
+        let moved = match mv.promotion {
+            Some(piece_type) => Piece::new(piece_type, piece.color),
+            None => piece,
+        };
+        self.put(mv.to, moved);
+
+        // castling, bring the rook over to the other side of the king
+        if let Some((rook_from, rook_to)) = mv.castling_rook() {
+            if let Some(rook) = self.remove(rook_from) {
+                self.put(rook_to, rook);
+            }
+        }
+
+        let mut castling = self.castling;
+        castling.update(piece, mv.from, mv.to);
+        self.set_castling(castling);
+        self.en_passant = if mv.kind == MoveKind::DoublePush {
+            Some(Square::new(
+                (mv.from.rank() + mv.to.rank()) / 2,
+                mv.from.file(),
+            ))
+        } else {
+            None
+        };
+        self.halfmove_clock = if piece.piece_type == PieceType::Pawn || mv.is_capture() {
+            0
+        } else {
+            self.halfmove_clock + 1
+        };
+        if self.side_to_move == PieceColor::Black {
+            self.fullmove_number += 1;
+        }
+        self.set_side_to_move(self.side_to_move.opposite());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn piece(piece_type: PieceType, color: PieceColor, x: u8, y: u8) -> (Square, Piece) {
+        (Square::new(x, y), Piece::new(piece_type, color))
+    }
+
+    fn pawn(color: PieceColor, x: u8, y: u8) -> (Square, Piece) {
+        piece(PieceType::Pawn, color, x, y)
+    }
+
+    fn position(pieces: &[(Square, Piece)], side_to_move: PieceColor) -> Position {
+        let mut position = Position::empty();
+        for &(square, piece) in pieces {
+            position.put(square, piece);
+        }
+        position.set_side_to_move(side_to_move);
+        position
+    }
+
+    /// Target squares of the pseudo-moves of the piece on `from`.
+    fn targets(position: &Position, from: Square) -> HashSet<(u8, u8)> {
+        position
+            .pseudo_moves()
+            .into_iter()
+            .filter(|mv| mv.from == from)
+            .map(|mv| (mv.to.rank(), mv.to.file()))
+            .collect()
+    }
+
+    /// Name, moving piece, the other pieces on the board and the expected target squares.
+    type Case<'a> = (
+        &'a str,
+        (Square, Piece),
+        Vec<(Square, Piece)>,
+        Vec<(u8, u8)>,
+    );
+
+    fn assert_cases(cases: Vec<Case>) {
+        for (name, moving, others, expected) in cases {
+            let mut pieces = others;
+            pieces.push(moving);
+            let position = position(&pieces, moving.1.color);
+            assert_eq!(
+                targets(&position, moving.0),
+                expected.into_iter().collect::<HashSet<(u8, u8)>>(),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn pawn_moves() {
+        use PieceColor::{Black, White};
+
+        assert_cases(vec![
+            (
+                "white single and double push",
+                pawn(White, 1, 3),
+                vec![],
+                vec![(2, 3), (3, 3)],
+            ),
+            (
+                "black single and double push",
+                pawn(Black, 6, 3),
+                vec![],
+                vec![(5, 3), (4, 3)],
+            ),
+            (
+                "no double push off the start rank",
+                pawn(White, 2, 3),
+                vec![],
+                vec![(3, 3)],
+            ),
+            (
+                "blocked by a friend",
+                pawn(White, 1, 3),
+                vec![pawn(White, 2, 3)],
+                vec![],
+            ),
+            (
+                "blocked by an enemy",
+                pawn(White, 1, 3),
+                vec![pawn(Black, 2, 3)],
+                vec![],
+            ),
+            (
+                "double push blocked",
+                pawn(White, 1, 3),
+                vec![pawn(Black, 3, 3)],
+                vec![(2, 3)],
+            ),
+            (
+                "black double push blocked",
+                pawn(Black, 6, 3),
+                vec![pawn(White, 4, 3)],
+                vec![(5, 3)],
+            ),
+            (
+                "captures both diagonals",
+                pawn(White, 3, 3),
+                vec![pawn(Black, 4, 2), pawn(Black, 4, 4)],
+                vec![(4, 3), (4, 2), (4, 4)],
+            ),
+            (
+                "black captures towards rank zero",
+                pawn(Black, 4, 3),
+                vec![pawn(White, 3, 2), pawn(White, 5, 4)],
+                vec![(3, 3), (3, 2)],
+            ),
+            (
+                "doesn't capture its own color",
+                pawn(White, 3, 3),
+                vec![pawn(White, 4, 2)],
+                vec![(4, 3)],
+            ),
+            (
+                "captures while blocked ahead",
+                pawn(White, 3, 0),
+                vec![pawn(Black, 4, 0), pawn(Black, 4, 1)],
+                vec![(4, 1)],
+            ),
+            ("edge of the board", pawn(White, 3, 7), vec![], vec![(4, 7)]),
+        ]);
+    }
+
+    #[test]
+    fn piece_captures() {
+        use PieceColor::{Black, White};
+        use PieceType::{Bishop, King, Knight, Queen, Rook};
+
+        assert_cases(vec![
+            (
+                "rook stops on and takes the first enemy",
+                piece(Rook, White, 0, 0),
+                vec![pawn(Black, 2, 0), pawn(White, 0, 2)],
+                vec![(1, 0), (2, 0), (0, 1)],
+            ),
+            (
+                "bishop takes along the diagonal",
+                piece(Bishop, White, 0, 2),
+                vec![pawn(Black, 1, 3), pawn(White, 1, 1)],
+                vec![(1, 3)],
+            ),
+            (
+                "queen takes orthogonally and diagonally",
+                piece(Queen, White, 0, 0),
+                vec![
+                    pawn(Black, 1, 0),
+                    pawn(Black, 1, 1),
+                    piece(Knight, Black, 0, 1),
+                ],
+                vec![(1, 0), (1, 1), (0, 1)],
+            ),
+            (
+                "knight jumps onto enemies only",
+                piece(Knight, White, 0, 1),
+                vec![pawn(Black, 2, 0), pawn(White, 2, 2)],
+                vec![(2, 0), (1, 3)],
+            ),
+            (
+                "king takes adjacent enemies",
+                piece(King, Black, 7, 0),
+                vec![pawn(White, 6, 0), pawn(Black, 6, 1)],
+                vec![(6, 0), (7, 1)],
+            ),
+        ]);
+    }
+
+    #[test]
+    fn pinned_piece_stays_put() {
+        use PieceColor::{Black, White};
+        use PieceType::{King, Knight, Rook};
+
+        let position = position(
+            &[
+                piece(King, White, 0, 4),
+                piece(Knight, White, 1, 4),
+                piece(Rook, Black, 7, 4),
+                piece(King, Black, 7, 0),
+            ],
+            White,
+        );
+        assert!(position
+            .legal_moves()
+            .iter()
+            .all(|mv| mv.from != Square::new(1, 4)));
+    }
+
+    #[test]
+    fn castling() {
+        use PieceColor::{Black, White};
+        use PieceType::{Bishop, King, Rook};
+
+        let mut start = position(
+            &[
+                piece(King, White, 0, 4),
+                piece(Rook, White, 0, 0),
+                piece(Rook, White, 0, 7),
+                piece(King, Black, 7, 4),
+            ],
+            White,
+        );
+        start.set_castling(CastlingRights::default());
+        let king = Square::new(0, 4);
+        assert!(start.legal_move(king, Square::new(0, 6), None).is_some());
+        assert!(start.legal_move(king, Square::new(0, 2), None).is_some());
+
+        // a bishop eyeing f1 stops castling through it, but not the other side
+        let mut attacked = start;
+        attacked.put(Square::new(3, 2), Piece::new(Bishop, Black));
+        assert!(attacked.legal_move(king, Square::new(0, 6), None).is_none());
+        assert!(attacked.legal_move(king, Square::new(0, 2), None).is_some());
+
+        let mut castled = start;
+        castled.make_move(start.legal_move(king, Square::new(0, 6), None).unwrap());
+        assert_eq!(
+            castled.piece_at(Square::new(0, 5)),
+            Some(Piece::new(Rook, White))
+        );
+        assert_eq!(castled.piece_at(Square::new(0, 7)), None);
+        assert_eq!(castled.castling().for_color(White), (false, false));
+
+        // moving a rook only loses its own side
+        let mut rook_moved = start;
+        rook_moved.make_move(
+            start
+                .legal_move(Square::new(0, 0), Square::new(1, 0), None)
+                .unwrap(),
+        );
+        assert_eq!(rook_moved.castling().for_color(White), (true, false));
+    }
+
+    #[test]
+    fn en_passant() {
+        use PieceColor::{Black, White};
+        use PieceType::King;
+
+        let mut position = position(
+            &[
+                piece(King, White, 0, 4),
+                pawn(White, 4, 3),
+                pawn(Black, 6, 4),
+                piece(King, Black, 7, 0),
+            ],
+            Black,
+        );
+        position.make_move(
+            position
+                .legal_move(Square::new(6, 4), Square::new(4, 4), None)
+                .unwrap(),
+        );
+        assert_eq!(position.en_passant(), Some(Square::new(5, 4)));
+
+        let mv = position
+            .legal_move(Square::new(4, 3), Square::new(5, 4), None)
+            .unwrap();
+        assert_eq!(mv.kind, MoveKind::EnPassant);
+        assert_eq!(mv.captured_square(), Some(Square::new(4, 4)));
+        position.make_move(mv);
+        assert_eq!(position.piece_at(Square::new(4, 4)), None);
+        assert_eq!(position.en_passant(), None);
+    }
+
+    #[test]
+    fn promotion() {
+        use PieceColor::{Black, White};
+        use PieceType::{King, Knight, Queen};
+
+        let mut position = position(
+            &[
+                piece(King, White, 0, 4),
+                pawn(White, 6, 0),
+                piece(King, Black, 7, 4),
+            ],
+            White,
+        );
+        let from = Square::new(6, 0);
+        let to = Square::new(7, 0);
+        let promotions = position
+            .legal_moves()
+            .into_iter()
+            .filter(|mv| mv.from == from)
+            .count();
+        assert_eq!(promotions, 4);
+        assert!(position.legal_move(from, to, None).is_none());
+
+        position.make_move(position.legal_move(from, to, Some(Knight)).unwrap());
+        assert_eq!(position.piece_at(to), Some(Piece::new(Knight, White)));
+        assert_ne!(position.piece_at(to), Some(Piece::new(Queen, White)));
+    }
+}
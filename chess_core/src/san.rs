@@ -0,0 +1,332 @@
+//! Standard Algebraic Notation, the move format PGN movetext is written in.
+
+use crate::moves::{Move, MoveKind};
+use crate::piece::PieceType;
+use crate::position::Position;
+use crate::square::Square;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SanError {
+    /// Not shaped like a SAN move.
+    Invalid(String),
+    /// No legal move matches.
+    Illegal(String),
+    /// More than one legal move matches.
+    Ambiguous(String),
+}
+
+impl fmt::Display for SanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SanError::Invalid(san) => write!(f, "{:?} isn't a SAN move", san),
+            SanError::Illegal(san) => write!(f, "{} isn't legal here", san),
+            SanError::Ambiguous(san) => write!(f, "{} matches more than one move", san),
+        }
+    }
+}
+
+impl std::error::Error for SanError {}
+
+impl Position {
+    /// SAN for `mv`, which has to be legal in this position.
+    pub fn san(&self, mv: Move) -> String {
+        let mut san = match mv.kind {
+            MoveKind::CastleKingSide => "O-O".to_string(),
+            MoveKind::CastleQueenSide => "O-O-O".to_string(),
+            _ => self.san_without_suffix(mv),
+        };
+
+        let mut next = *self;
+        next.make_move(mv);
+        if next.is_in_check(next.side_to_move()) {
+            san.push(if next.legal_moves().is_empty() {
+                '#'
+            } else {
+                '+'
+            });
+        }
+        san
+    }
+
+    /// The legal move `san` stands for. Check, mate and annotation suffixes are ignored, as are
+    /// redundant disambiguations.
+    pub fn parse_san(&self, san: &str) -> Result<Move, SanError> {
+        let invalid = || SanError::Invalid(san.to_string());
+        let body = san.trim_end_matches(|c| matches!(c, '+' | '#' | '!' | '?'));
+
+        let castling = match body {
+            "O-O" | "0-0" => Some(MoveKind::CastleKingSide),
+            "O-O-O" | "0-0-0" => Some(MoveKind::CastleQueenSide),
+            _ => None,
+        };
+        let matches = if let Some(kind) = castling {
+            self.legal_moves()
+                .into_iter()
+                .filter(|mv| mv.kind == kind)
+                .collect::<Vec<Move>>()
+        } else {
+            let (piece, rest) = match body.chars().next() {
+                Some(c @ ('N' | 'B' | 'R' | 'Q' | 'K')) => {
+                    (PieceType::from_char(c).ok_or_else(invalid)?, &body[1..])
+                }
+                _ => (PieceType::Pawn, body),
+            };
+            // the promotion comes last, with or without the `=`
+            let (rest, promotion) = match rest.chars().last() {
+                Some(c @ ('N' | 'B' | 'R' | 'Q')) if piece == PieceType::Pawn => (
+                    rest[..rest.len() - 1].trim_end_matches('='),
+                    PieceType::from_char(c),
+                ),
+                _ => (rest, None),
+            };
+            if !rest.is_ascii() || rest.len() < 2 {
+                return Err(invalid());
+            }
+            let (hint, to) = rest.split_at(rest.len() - 2);
+            let to = to.parse::<Square>().map_err(|_| invalid())?;
+            let hint = hint.trim_end_matches('x');
+            let (mut file, mut rank) = (None, None);
+            for c in hint.bytes() {
+                match c {
+                    b'a'..=b'h' if file.is_none() && rank.is_none() => file = Some(c - b'a'),
+                    b'1'..=b'8' if rank.is_none() => rank = Some(c - b'1'),
+                    _ => return Err(invalid()),
+                }
+            }
+
+            self.legal_moves()
+                .into_iter()
+                .filter(|mv| {
+                    mv.piece == piece
+                        && mv.to == to
+                        && mv.promotion == promotion
+                        && file.map_or(true, |file| mv.from.file() == file)
+                        && rank.map_or(true, |rank| mv.from.rank() == rank)
+                })
+                .collect()
+        };
+
+        match matches[..] {
+            [mv] => Ok(mv),
+            [] => Err(SanError::Illegal(san.to_string())),
+            _ => Err(SanError::Ambiguous(san.to_string())),
+        }
+    }
+
+    fn san_without_suffix(&self, mv: Move) -> String {
+        let mut san = String::new();
+        if mv.piece == PieceType::Pawn {
+            if mv.is_capture() {
+                san.push((b'a' + mv.from.file()) as char);
+            }
+        } else {
+            san.push(mv.piece.to_char());
+            // other pieces of the same type that could go to the same square
+            let rivals = self
+                .legal_moves()
+                .into_iter()
+                .filter(|other| {
+                    other.piece == mv.piece && other.to == mv.to && other.from != mv.from
+                })
+                .collect::<Vec<Move>>();
+            if !rivals.is_empty() {
+                let from = mv.from.to_string();
+                if rivals
+                    .iter()
+                    .all(|other| other.from.file() != mv.from.file())
+                {
+                    san.push_str(&from[..1]);
+                } else if rivals
+                    .iter()
+                    .all(|other| other.from.rank() != mv.from.rank())
+                {
+                    san.push_str(&from[1..]);
+                } else {
+                    san.push_str(&from);
+                }
+            }
+        }
+
+        if mv.is_capture() {
+            san.push('x');
+        }
+        san.push_str(&mv.to.to_string());
+        if let Some(promotion) = mv.promotion {
+            san.push('=');
+            san.push(promotion.to_char());
+        }
+        san
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn san_of(fen: &str, from: &str, to: &str, promotion: Option<PieceType>) -> String {
+        let position = Position::from_fen(fen).unwrap();
+        let mv = position
+            .legal_move(from.parse().unwrap(), to.parse().unwrap(), promotion)
+            .unwrap();
+        position.san(mv)
+    }
+
+    #[test]
+    fn renders() {
+        let cases = [
+            // plain moves and captures
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                "g1",
+                "f3",
+                None,
+                "Nf3",
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                "e2",
+                "e4",
+                None,
+                "e4",
+            ),
+            (
+                "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2",
+                "e4",
+                "d5",
+                None,
+                "exd5",
+            ),
+            (
+                "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+                "e5",
+                "f6",
+                None,
+                "exf6",
+            ),
+            // disambiguation by file, by rank and by both
+            ("4k3/8/8/8/8/8/4K3/R6R w - - 0 1", "a1", "d1", None, "Rad1"),
+            ("4k3/R7/8/8/8/8/8/R3K3 w - - 0 1", "a1", "a4", None, "R1a4"),
+            (
+                "1k6/8/8/8/4Q2Q/8/8/K6Q w - - 0 1",
+                "h4",
+                "e1",
+                None,
+                "Qh4e1",
+            ),
+            ("1k6/8/8/8/4Q2Q/8/8/K6Q w - - 0 1", "h1", "e1", None, "Q1e1"),
+            ("1k6/8/8/8/4Q2Q/8/8/K6Q w - - 0 1", "h4", "e7", None, "Qhe7"),
+            ("4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1", "b1", "d2", None, "Nbd2"),
+            // a pinned knight doesn't count as a rival
+            (
+                "4k3/4r3/8/8/8/1N6/4N3/4K3 w - - 0 1",
+                "b3",
+                "d4",
+                None,
+                "Nd4",
+            ),
+            // castling, checks and mate
+            (
+                "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1",
+                "e1",
+                "g1",
+                None,
+                "O-O",
+            ),
+            (
+                "r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1",
+                "e8",
+                "c8",
+                None,
+                "O-O-O",
+            ),
+            (
+                "6k1/5ppp/8/8/8/8/8/R3K3 w Q - 0 1",
+                "e1",
+                "c1",
+                None,
+                "O-O-O",
+            ),
+            (
+                "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1",
+                "a1",
+                "a8",
+                None,
+                "Ra8#",
+            ),
+            // promotions
+            (
+                "4k3/P7/8/8/8/8/8/4K3 w - - 0 1",
+                "a7",
+                "a8",
+                Some(PieceType::Queen),
+                "a8=Q+",
+            ),
+            (
+                "1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1",
+                "a7",
+                "b8",
+                Some(PieceType::Knight),
+                "axb8=N",
+            ),
+        ];
+        for (fen, from, to, promotion, san) in cases {
+            assert_eq!(
+                san_of(fen, from, to, promotion),
+                san,
+                "{} {}{}",
+                fen,
+                from,
+                to
+            );
+        }
+    }
+
+    #[test]
+    fn parses_what_it_renders() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "1k6/8/8/8/4Q2Q/8/8/K6Q w - - 0 1",
+        ] {
+            let position = Position::from_fen(fen).unwrap();
+            for mv in position.legal_moves() {
+                assert_eq!(position.parse_san(&position.san(mv)), Ok(mv));
+            }
+        }
+    }
+
+    #[test]
+    fn parses_loose_input() {
+        let position = Position::from_fen("r3k2r/P7/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        let parse = |san: &str| position.parse_san(san).map(|mv| position.san(mv));
+        assert_eq!(parse("0-0"), Ok("O-O".to_string()));
+        assert_eq!(parse("axb8Q"), Err(SanError::Illegal("axb8Q".to_string())));
+        assert_eq!(parse("a8Q!?"), Err(SanError::Illegal("a8Q!?".to_string())));
+        assert_eq!(parse("Ra1b1"), Ok("Rb1".to_string()));
+        assert_eq!(parse("Kd1+"), Ok("Kd1".to_string()));
+    }
+
+    #[test]
+    fn errors() {
+        let position = Position::from_fen("4k3/8/8/8/8/8/4K3/R6R w - - 0 1").unwrap();
+        assert_eq!(
+            position.parse_san("Rd1"),
+            Err(SanError::Ambiguous("Rd1".to_string()))
+        );
+        assert_eq!(
+            position.parse_san("Nf3"),
+            Err(SanError::Illegal("Nf3".to_string()))
+        );
+        assert_eq!(
+            position.parse_san("O-O"),
+            Err(SanError::Illegal("O-O".to_string()))
+        );
+        for invalid in ["", "R", "Rz9", "Qi1", "R1a1a1", "hello"] {
+            assert_eq!(
+                position.parse_san(invalid),
+                Err(SanError::Invalid(invalid.to_string()))
+            );
+        }
+    }
+}
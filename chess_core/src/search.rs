@@ -0,0 +1,428 @@
+//! Negamax alpha-beta search with iterative deepening, a transposition table and a quiescence
+//! search on captures.
+
+use crate::eval::{evaluate, piece_value};
+use crate::moves::Move;
+use crate::piece::PieceType;
+use crate::position::Position;
+use crate::transposition::{Bound, Entry, TranspositionTable};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Score of being mated right now, mates further away score closer to zero.
+pub const MATE: i32 = 30_000;
+const INFINITY: i32 = MATE + 1;
+/// Scores past this are mates.
+const MATE_BOUND: i32 = MATE - 1_000;
+const MAX_DEPTH: u32 = 64;
+
+/// When to stop searching, whichever limit is hit first. Without any the search only stops
+/// through `Search::stop_flag`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SearchLimits {
+    pub depth: Option<u32>,
+    pub time: Option<Duration>,
+    pub nodes: Option<u64>,
+}
+
+impl SearchLimits {
+    pub fn depth(depth: u32) -> Self {
+        Self {
+            depth: Some(depth),
+            ..Self::default()
+        }
+    }
+
+    pub fn time(time: Duration) -> Self {
+        Self {
+            time: Some(time),
+            ..Self::default()
+        }
+    }
+}
+
+/// Outcome of a completed iteration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchInfo {
+    pub depth: u32,
+    /// Centipawns for the side to move, see `mate_in` for mate scores.
+    pub score: i32,
+    pub nodes: u64,
+    pub elapsed: Duration,
+    /// Principal variation, the best move first.
+    pub pv: Vec<Move>,
+}
+
+impl SearchInfo {
+    pub fn best_move(&self) -> Option<Move> {
+        self.pv.first().copied()
+    }
+
+    /// Moves until mate, negative when the side to move is getting mated.
+    pub fn mate_in(&self) -> Option<i32> {
+        if self.score > MATE_BOUND {
+            Some((MATE - self.score + 1) / 2)
+        } else if self.score < -MATE_BOUND {
+            Some(-(MATE + self.score) / 2)
+        } else {
+            None
+        }
+    }
+}
+
+pub struct Search {
+    limits: SearchLimits,
+    stop: Arc<AtomicBool>,
+    started: Instant,
+    nodes: u64,
+    aborted: bool,
+    table: TranspositionTable,
+    /// Hashes of the positions played before the searched one, see `set_history`.
+    history: Vec<u64>,
+    /// `history` followed by the positions on the line being searched.
+    path: Vec<u64>,
+}
+
+impl Search {
+    pub fn new(limits: SearchLimits) -> Self {
+        Self {
+            limits,
+            stop: Arc::new(AtomicBool::new(false)),
+            started: Instant::now(),
+            nodes: 0,
+            aborted: false,
+            table: TranspositionTable::default(),
+            history: Vec::new(),
+            path: Vec::new(),
+        }
+    }
+
+    /// Hashes of the positions the game went through before the one to search, oldest first,
+    /// so going back to one of them is scored as a draw by repetition.
+    pub fn set_history(&mut self, history: impl IntoIterator<Item = u64>) {
+        self.history = history.into_iter().collect();
+    }
+
+    /// Setting the flag makes a running search return its last completed iteration.
+    pub fn stop_flag(&self) -> Arc<AtomicBool> {
+        self.stop.clone()
+    }
+
+    /// Searches deeper and deeper until a limit is hit, calling `on_iteration` after each
+    /// completed depth. `None` when the side to move has no legal moves.
+    pub fn run(
+        &mut self,
+        position: &Position,
+        mut on_iteration: impl FnMut(&SearchInfo),
+    ) -> Option<SearchInfo> {
+        self.started = Instant::now();
+        self.nodes = 0;
+        self.aborted = false;
+        self.path.clone_from(&self.history);
+
+        let mut best: Option<SearchInfo> = None;
+        let max_depth = self.limits.depth.unwrap_or(MAX_DEPTH).clamp(1, MAX_DEPTH);
+        for depth in 1..=max_depth {
+            let mut pv = best.as_ref().map_or_else(Vec::new, |info| info.pv.clone());
+            let score = self.negamax(position, depth, 0, -INFINITY, INFINITY, &mut pv);
+            // a cut short iteration is only trusted when there is nothing better
+            if self.aborted && best.is_some() {
+                break;
+            }
+            if pv.is_empty() {
+                return None;
+            }
+            let info = SearchInfo {
+                depth,
+                score,
+                nodes: self.nodes,
+                elapsed: self.started.elapsed(),
+                pv,
+            };
+            on_iteration(&info);
+            best = Some(info);
+            if self.aborted || score.abs() > MATE_BOUND {
+                break;
+            }
+        }
+        best
+    }
+
+    fn should_stop(&mut self) -> bool {
+        if self.aborted || self.stop.load(Ordering::Relaxed) {
+            self.aborted = true;
+            return true;
+        }
+        // the clock is only looked at every so often
+        if self.nodes % 1024 == 0 {
+            let out_of_time = self
+                .limits
+                .time
+                .map_or(false, |time| self.started.elapsed() >= time);
+            let out_of_nodes = self.limits.nodes.map_or(false, |nodes| self.nodes >= nodes);
+            self.aborted = out_of_time || out_of_nodes;
+        }
+        self.aborted
+    }
+
+    /// `pv` comes in holding the line to try first and leaves holding the best line found.
+    fn negamax(
+        &mut self,
+        position: &Position,
+        depth: u32,
+        ply: i32,
+        mut alpha: i32,
+        beta: i32,
+        pv: &mut Vec<Move>,
+    ) -> i32 {
+        let hash = position.hash();
+        let drawn = position.halfmove_clock() >= 100
+            || position.has_insufficient_material()
+            || self.is_repetition(position, hash);
+        if ply > 0 && drawn {
+            pv.clear();
+            return 0;
+        }
+        if depth == 0 {
+            pv.clear();
+            return self.quiescence(position, alpha, beta);
+        }
+        self.nodes += 1;
+
+        // the root is always searched so there is a move to play
+        let entry = self.table.probe(hash);
+        if let Some(entry) = entry.filter(|entry| ply > 0 && entry.depth >= depth) {
+            let score = from_table(entry.score, ply);
+            let usable = match entry.bound {
+                Bound::Exact => true,
+                Bound::Lower => score >= beta,
+                Bound::Upper => score <= alpha,
+            };
+            if usable {
+                pv.clear();
+                pv.extend(entry.best_move);
+                return score;
+            }
+        }
+
+        let mut moves = position.legal_moves();
+        if moves.is_empty() {
+            pv.clear();
+            return if position.is_in_check(position.side_to_move()) {
+                -MATE + ply
+            } else {
+                0
+            };
+        }
+        let first = pv.first().copied();
+        order_moves(
+            &mut moves,
+            first.or_else(|| entry.and_then(|entry| entry.best_move)),
+        );
+
+        let original_alpha = alpha;
+        let mut best = -INFINITY;
+        self.path.push(hash);
+        let mut line = pv.get(1..).map_or_else(Vec::new, |rest| rest.to_vec());
+        for (i, mv) in moves.into_iter().enumerate() {
+            if i > 0 {
+                line.clear();
+            }
+            let mut next = *position;
+            next.make_move(mv);
+            let score = -self.negamax(&next, depth - 1, ply + 1, -beta, -alpha, &mut line);
+            if self.should_stop() && i > 0 {
+                break;
+            }
+            if score > best {
+                best = score;
+                pv.clear();
+                pv.push(mv);
+                pv.extend_from_slice(&line);
+            }
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+        self.path.pop();
+
+        // scores of a search cut short can't be trusted
+        if !self.aborted {
+            let bound = if best >= beta {
+                Bound::Lower
+            } else if best > original_alpha {
+                Bound::Exact
+            } else {
+                Bound::Upper
+            };
+            self.table.store(Entry {
+                hash,
+                depth,
+                score: to_table(best, ply),
+                bound,
+                best_move: pv.first().copied(),
+            });
+        }
+        best
+    }
+
+    /// Whether `position` already came up since the last capture or pawn move, with the same
+    /// side to move.
+    fn is_repetition(&self, position: &Position, hash: u64) -> bool {
+        self.path
+            .iter()
+            .rev()
+            .take(position.halfmove_clock() as usize)
+            .skip(1)
+            .step_by(2)
+            .any(|&earlier| earlier == hash)
+    }
+
+    /// Only captures and promotions are searched so the evaluation isn't taken in the middle of
+    /// an exchange.
+    fn quiescence(&mut self, position: &Position, mut alpha: i32, beta: i32) -> i32 {
+        self.nodes += 1;
+        let stand_pat = evaluate(position);
+        if stand_pat >= beta {
+            return stand_pat;
+        }
+        alpha = alpha.max(stand_pat);
+
+        let mut moves = position
+            .legal_moves()
+            .into_iter()
+            .filter(|mv| mv.is_capture() || mv.promotion.is_some())
+            .collect::<Vec<Move>>();
+        order_moves(&mut moves, None);
+        for mv in moves {
+            let mut next = *position;
+            next.make_move(mv);
+            let score = -self.quiescence(&next, -beta, -alpha);
+            if self.should_stop() {
+                break;
+            }
+            if score >= beta {
+                return score;
+            }
+            alpha = alpha.max(score);
+        }
+        alpha
+    }
+}
+
+/// Mate scores count from the root, the table keeps them counted from the stored position so
+/// they stay right when it is reached at another ply.
+fn to_table(score: i32, ply: i32) -> i32 {
+    if score > MATE_BOUND {
+        score + ply
+    } else if score < -MATE_BOUND {
+        score - ply
+    } else {
+        score
+    }
+}
+
+fn from_table(score: i32, ply: i32) -> i32 {
+    if score > MATE_BOUND {
+        score - ply
+    } else if score < -MATE_BOUND {
+        score + ply
+    } else {
+        score
+    }
+}
+
+/// The move from the previous iteration or the table first, then captures of the most valuable piece by the
+/// least valuable one, then the rest.
+fn order_moves(moves: &mut [Move], first: Option<Move>) {
+    moves.sort_by_cached_key(|&mv| {
+        if Some(mv) == first {
+            return i32::MIN;
+        }
+        let capture = mv.captured.map_or(0, |captured| {
+            10 * piece_value(captured) - piece_value(mv.piece)
+        });
+        let promotion = mv.promotion.map_or(0, piece_value);
+        -(capture + promotion) + i32::from(mv.piece == PieceType::King)
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn best(fen: &str, depth: u32) -> SearchInfo {
+        let position = Position::from_fen(fen).unwrap();
+        Search::new(SearchLimits::depth(depth))
+            .run(&position, |_| {})
+            .unwrap()
+    }
+
+    #[test]
+    fn finds_mates() {
+        // back rank mate in one
+        let info = best("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 3);
+        assert_eq!(info.best_move().unwrap().to_uci(), "a1a8");
+        assert_eq!(info.mate_in(), Some(1));
+
+        // mate in two, the first rook is given up to open the back rank for the second
+        let info = best("r5k1/5ppp/8/8/8/8/3R1PPP/3R2K1 w - - 0 1", 4);
+        assert_eq!(info.best_move().unwrap().to_uci(), "d2d8");
+        assert_eq!(info.mate_in(), Some(2));
+    }
+
+    #[test]
+    fn wins_material() {
+        // the knight forks king and queen
+        let info = best("4k3/8/8/5N2/2q5/8/8/4K3 w - - 0 1", 4);
+        assert_eq!(info.best_move().unwrap().to_uci(), "f5d6");
+        // and doesn't take a defended pawn with the queen
+        let info = best("4k3/2p5/3p4/8/8/8/8/3QK3 w - - 0 1", 3);
+        assert_ne!(info.best_move().unwrap().to_uci(), "d1d6");
+    }
+
+    #[test]
+    fn no_moves() {
+        let position = Position::from_fen("k7/8/1Q6/8/8/8/8/4K3 b - - 0 1").unwrap();
+        assert!(Search::new(SearchLimits::depth(2))
+            .run(&position, |_| {})
+            .is_none());
+    }
+
+    #[test]
+    fn stops() {
+        let mut search = Search::new(SearchLimits::time(Duration::from_millis(50)));
+        let mut depths = Vec::new();
+        let info = search
+            .run(&Position::start(), |info| depths.push(info.depth))
+            .unwrap();
+        assert!(info.best_move().is_some());
+        assert_eq!(depths, (1..=info.depth).collect::<Vec<u32>>());
+
+        let mut search = Search::new(SearchLimits::default());
+        search.stop_flag().store(true, Ordering::Relaxed);
+        assert_eq!(search.run(&Position::start(), |_| {}).unwrap().depth, 1);
+    }
+
+    #[test]
+    fn knows_repetitions() {
+        // a rook down, the black king can only go back and forth between g8 and h8
+        let mut game =
+            crate::game::Game::new(Position::from_fen("6k1/R7/8/8/8/8/8/2K5 w - - 0 1").unwrap());
+        for uci in ["c1c2", "g8h8", "c2c1"] {
+            let mv = Move::from_uci(game.position(), uci).unwrap();
+            game.make_move(mv).unwrap();
+        }
+
+        let info = best(&game.position().to_fen(), 2);
+        assert!(info.score < -300);
+        // with the game so far Kg8 repeats the position it started from
+        let mut search = Search::new(SearchLimits::depth(2));
+        search.set_history(game.previous_hashes());
+        let info = search.run(game.position(), |_| {}).unwrap();
+        assert_eq!(info.best_move().unwrap().to_uci(), "h8g8");
+        assert_eq!(info.score, 0);
+    }
+}
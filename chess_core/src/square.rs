@@ -0,0 +1,79 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// A square on the board, rank 0 is white's back rank and file 0 is the a-file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Square(u8);
+
+impl Square {
+    pub const fn new(rank: u8, file: u8) -> Self {
+        debug_assert!(rank < 8 && file < 8);
+        Self(rank * 8 + file)
+    }
+
+    pub const fn from_index(index: usize) -> Self {
+        debug_assert!(index < 64);
+        Self(index as u8)
+    }
+
+    pub const fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    pub const fn rank(self) -> u8 {
+        self.0 / 8
+    }
+
+    pub const fn file(self) -> u8 {
+        self.0 % 8
+    }
+
+    /// The square `ranks` and `files` away, if that is still on the board.
+    pub fn offset(self, ranks: i8, files: i8) -> Option<Square> {
+        let rank = self.rank() as i8 + ranks;
+        let file = self.file() as i8 + files;
+        if (0..8).contains(&rank) && (0..8).contains(&file) {
+            Some(Square::new(rank as u8, file as u8))
+        } else {
+            None
+        }
+    }
+
+    pub fn all() -> impl Iterator<Item = Square> {
+        (0..64).map(Square)
+    }
+}
+
+impl fmt::Display for Square {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}",
+            (b'a' + self.file()) as char,
+            (b'1' + self.rank()) as char
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseSquareError(pub String);
+
+impl fmt::Display for ParseSquareError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid square {:?}", self.0)
+    }
+}
+
+impl std::error::Error for ParseSquareError {}
+
+impl FromStr for Square {
+    type Err = ParseSquareError;
+
+    /// Reads a square name like `e4`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.as_bytes() {
+            &[file @ b'a'..=b'h', rank @ b'1'..=b'8'] => Ok(Square::new(rank - b'1', file - b'a')),
+            _ => Err(ParseSquareError(s.to_string())),
+        }
+    }
+}
@@ -0,0 +1,105 @@
+//! Fixed-size table of search results keyed by Zobrist hash, so a position reached again through
+//! a different move order, or again at the next depth, doesn't have to be searched from scratch.
+
+use crate::moves::Move;
+
+/// How the stored score relates to the real one, a search cut off by alpha-beta only knows one
+/// side of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    /// The score is at least this, the search failed high.
+    Lower,
+    /// The score is at most this, no move reached alpha.
+    Upper,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry {
+    pub hash: u64,
+    pub depth: u32,
+    pub score: i32,
+    pub bound: Bound,
+    pub best_move: Option<Move>,
+}
+
+pub struct TranspositionTable {
+    entries: Vec<Option<Entry>>,
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        Self::new(16)
+    }
+}
+
+impl TranspositionTable {
+    /// A table taking about `megabytes` of memory, rounded down to a power of two entries.
+    pub fn new(megabytes: usize) -> Self {
+        let wanted = (megabytes << 20) / std::mem::size_of::<Option<Entry>>();
+        let len = if wanted.is_power_of_two() {
+            wanted
+        } else {
+            (wanted.next_power_of_two() / 2).max(1)
+        };
+        Self {
+            entries: vec![None; len],
+        }
+    }
+
+    fn slot(&self, hash: u64) -> usize {
+        hash as usize & (self.entries.len() - 1)
+    }
+
+    /// The entry stored for `hash`, if another position hasn't taken its slot since.
+    pub fn probe(&self, hash: u64) -> Option<Entry> {
+        self.entries[self.slot(hash)].filter(|entry| entry.hash == hash)
+    }
+
+    /// Stores `entry`, replacing what was in its slot unless that is the same position searched
+    /// deeper.
+    pub fn store(&mut self, entry: Entry) {
+        let slot = self.slot(entry.hash);
+        match self.entries[slot] {
+            Some(old) if old.hash == entry.hash && old.depth > entry.depth => {}
+            _ => self.entries[slot] = Some(entry),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.iter_mut().for_each(|entry| *entry = None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stores_and_replaces() {
+        let mut table = TranspositionTable::new(1);
+        assert!(table.entries.len().is_power_of_two());
+        let entry = |hash, depth| Entry {
+            hash,
+            depth,
+            score: 10,
+            bound: Bound::Exact,
+            best_move: None,
+        };
+
+        table.store(entry(42, 3));
+        assert_eq!(table.probe(42), Some(entry(42, 3)));
+        assert_eq!(table.probe(43), None);
+        // a shallower result for the same position keeps the deeper one
+        table.store(entry(42, 1));
+        assert_eq!(table.probe(42).unwrap().depth, 3);
+        // another position in the same slot takes it over
+        let other = 42 + table.entries.len() as u64;
+        table.store(entry(other, 1));
+        assert_eq!(table.probe(42), None);
+        assert_eq!(table.probe(other), Some(entry(other, 1)));
+
+        table.clear();
+        assert_eq!(table.probe(other), None);
+    }
+}
@@ -0,0 +1,186 @@
+//! Client side of the Universal Chess Interface, for driving an engine running as a child
+//! process.
+
+use crate::fen::START_FEN;
+use crate::game::Game;
+use crate::moves::Move;
+use std::ffi::OsStr;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::time::Duration;
+
+#[derive(Debug)]
+pub enum UciError {
+    Io(io::Error),
+    /// The engine closed its output before answering.
+    Disconnected,
+    /// The engine answered with a move that isn't legal in the game.
+    IllegalMove(String),
+}
+
+impl fmt::Display for UciError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UciError::Io(err) => write!(f, "engine i/o failed: {}", err),
+            UciError::Disconnected => write!(f, "the engine quit"),
+            UciError::IllegalMove(mv) => write!(f, "the engine played an illegal move {}", mv),
+        }
+    }
+}
+
+impl std::error::Error for UciError {}
+
+impl From<io::Error> for UciError {
+    fn from(err: io::Error) -> Self {
+        UciError::Io(err)
+    }
+}
+
+/// The `position` command for the current state of `game`.
+pub fn position_command(game: &Game) -> String {
+    let fen = game.initial_position().to_fen();
+    let mut command = if fen == START_FEN {
+        "position startpos".to_string()
+    } else {
+        format!("position fen {}", fen)
+    };
+    let mut moves = game.moves().peekable();
+    if moves.peek().is_some() {
+        command.push_str(" moves");
+        for mv in moves {
+            command.push(' ');
+            command.push_str(&mv.to_uci());
+        }
+    }
+    command
+}
+
+/// An engine process spoken to over its stdin and stdout.
+pub struct UciEngine {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    /// From the engine's `id name`.
+    pub name: Option<String>,
+}
+
+impl UciEngine {
+    /// Starts `program` and waits for it to finish the `uci` handshake.
+    pub fn spawn<S: AsRef<OsStr>>(program: S, args: &[S]) -> Result<Self, UciError> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+        let mut engine = Self {
+            child,
+            stdin,
+            stdout,
+            name: None,
+        };
+
+        engine.send("uci")?;
+        loop {
+            let line = engine.read_line()?;
+            if let Some(name) = line.strip_prefix("id name ") {
+                engine.name = Some(name.to_string());
+            } else if line == "uciok" {
+                break;
+            }
+        }
+        engine.wait_ready()?;
+        Ok(engine)
+    }
+
+    pub fn send(&mut self, command: &str) -> Result<(), UciError> {
+        writeln!(self.stdin, "{}", command)?;
+        self.stdin.flush()?;
+        Ok(())
+    }
+
+    fn read_line(&mut self) -> Result<String, UciError> {
+        let mut line = String::new();
+        if self.stdout.read_line(&mut line)? == 0 {
+            return Err(UciError::Disconnected);
+        }
+        Ok(line.trim().to_string())
+    }
+
+    fn wait_ready(&mut self) -> Result<(), UciError> {
+        self.send("isready")?;
+        while self.read_line()? != "readyok" {}
+        Ok(())
+    }
+
+    /// Tells the engine the next position belongs to a different game.
+    pub fn new_game(&mut self) -> Result<(), UciError> {
+        self.send("ucinewgame")?;
+        self.wait_ready()
+    }
+
+    /// Lets the engine think about the current position of `game` for `movetime` and returns
+    /// its move, `None` when it has none to play.
+    pub fn best_move(&mut self, game: &Game, movetime: Duration) -> Result<Option<Move>, UciError> {
+        self.send(&position_command(game))?;
+        self.send(&format!("go movetime {}", movetime.as_millis()))?;
+        let best = loop {
+            let line = self.read_line()?;
+            if let Some(rest) = line.strip_prefix("bestmove") {
+                break rest
+                    .split_whitespace()
+                    .next()
+                    .unwrap_or("(none)")
+                    .to_string();
+            }
+        };
+        if best == "(none)" || best == "0000" {
+            return Ok(None);
+        }
+        match Move::from_uci(game.position(), &best) {
+            Some(mv) => Ok(Some(mv)),
+            None => Err(UciError::IllegalMove(best)),
+        }
+    }
+}
+
+impl Drop for UciEngine {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        // give it a moment to quit on its own before pulling the plug
+        for _ in 0..20 {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::Position;
+
+    #[test]
+    fn position_commands() {
+        let mut game = Game::default();
+        assert_eq!(position_command(&game), "position startpos");
+        let mv = Move::from_uci(game.position(), "e2e4").unwrap();
+        game.make_move(mv).unwrap();
+        assert_eq!(position_command(&game), "position startpos moves e2e4");
+
+        let fen = "4k3/P7/8/8/8/8/8/4K3 w - - 0 1";
+        let mut game = Game::new(Position::from_fen(fen).unwrap());
+        let mv = Move::from_uci(game.position(), "a7a8q").unwrap();
+        game.make_move(mv).unwrap();
+        assert_eq!(
+            position_command(&game),
+            format!("position fen {} moves a7a8q", fen)
+        );
+    }
+}
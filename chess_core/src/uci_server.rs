@@ -0,0 +1,246 @@
+//! Engine side of the Universal Chess Interface: reads GUI commands and answers with moves from
+//! the built-in search.
+
+use crate::fen::FenError;
+use crate::game::Game;
+use crate::moves::Move;
+use crate::piece::PieceColor;
+use crate::position::Position;
+use crate::search::{Search, SearchInfo, SearchLimits};
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+pub const ENGINE_NAME: &str = "bevy_test";
+pub const ENGINE_AUTHOR: &str = "the bevy_test authors";
+
+/// Moves a game is assumed to still last when the GUI doesn't say with `movestogo`.
+const MOVES_TO_GO: u32 = 30;
+
+/// Shared so the search thread can report while commands keep coming in.
+type Output = Arc<Mutex<dyn Write + Send>>;
+
+/// The search running on its own thread and the flag that cuts it short.
+struct RunningSearch {
+    thread: JoinHandle<()>,
+    stop: Arc<AtomicBool>,
+}
+
+pub struct UciServer {
+    output: Output,
+    game: Game,
+    search: Option<RunningSearch>,
+}
+
+impl UciServer {
+    pub fn new(output: impl Write + Send + 'static) -> Self {
+        Self {
+            output: Arc::new(Mutex::new(output)),
+            game: Game::default(),
+            search: None,
+        }
+    }
+
+    /// Handles one line from the GUI, `false` once it asked to quit.
+    pub fn handle(&mut self, line: &str) -> bool {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("uci") => {
+                self.send(&format!("id name {}", ENGINE_NAME));
+                self.send(&format!("id author {}", ENGINE_AUTHOR));
+                self.send("uciok");
+            }
+            Some("isready") => self.send("readyok"),
+            Some("ucinewgame") => {
+                self.stop_search();
+                self.game = Game::default();
+            }
+            Some("position") => {
+                self.stop_search();
+                match parse_position(words.collect()) {
+                    Ok((game, illegal)) => {
+                        self.game = game;
+                        if let Some(err) = illegal {
+                            self.send(&format!("info string {}", err));
+                        }
+                    }
+                    Err(err) => self.send(&format!("info string {}", err)),
+                }
+            }
+            Some("go") => {
+                self.stop_search();
+                let limits = parse_limits(words.collect(), self.game.side_to_move());
+                self.start_search(limits);
+            }
+            Some("stop") => self.stop_search(),
+            Some("quit") => {
+                self.stop_search();
+                return false;
+            }
+            // options, debug and anything unknown are ignored as the protocol asks
+            _ => {}
+        }
+        true
+    }
+
+    fn send(&self, line: &str) {
+        send(&self.output, line);
+    }
+
+    fn start_search(&mut self, limits: SearchLimits) {
+        let mut search = Search::new(limits);
+        search.set_history(self.game.previous_hashes());
+        let stop = search.stop_flag();
+        let position = *self.game.position();
+        let output = self.output.clone();
+        let thread = std::thread::spawn(move || {
+            let best = search.run(&position, |info| send(&output, &info_line(info)));
+            let best_move = best.and_then(|info| info.best_move());
+            send(
+                &output,
+                &format!(
+                    "bestmove {}",
+                    best_move.map_or("(none)".to_string(), |mv| mv.to_uci())
+                ),
+            );
+        });
+        self.search = Some(RunningSearch { thread, stop });
+    }
+
+    /// Cuts a search still running short and waits for its `bestmove`, so an infinite one
+    /// can't hold up the next command.
+    fn stop_search(&mut self) {
+        if let Some(search) = self.search.take() {
+            search.stop.store(true, Ordering::Relaxed);
+            let _ = search.thread.join();
+        }
+    }
+}
+
+fn send(output: &Output, line: &str) {
+    let mut output = output.lock().unwrap();
+    let _ = writeln!(output, "{}", line);
+    let _ = output.flush();
+}
+
+fn info_line(info: &SearchInfo) -> String {
+    let score = match info.mate_in() {
+        Some(moves) => format!("mate {}", moves),
+        None => format!("cp {}", info.score),
+    };
+    let pv = info
+        .pv
+        .iter()
+        .map(Move::to_uci)
+        .collect::<Vec<String>>()
+        .join(" ");
+    let millis = info.elapsed.as_millis();
+    let nps = info.nodes as u128 * 1000 / millis.max(1);
+    format!(
+        "info depth {} score {} nodes {} time {} nps {} pv {}",
+        info.depth, score, info.nodes, millis, nps, pv
+    )
+}
+
+/// `position [startpos | fen <fen>] [moves <move>...]`, moves stop at the first illegal one
+/// which comes back next to the game played up to it.
+fn parse_position(words: Vec<&str>) -> Result<(Game, Option<String>), String> {
+    let moves_at = words
+        .iter()
+        .position(|&word| word == "moves")
+        .unwrap_or(words.len());
+    let position = match words.first() {
+        Some(&"startpos") => Position::start(),
+        Some(&"fen") => Position::from_fen(&words[1..moves_at].join(" "))
+            .map_err(|err: FenError| err.to_string())?,
+        _ => return Err("position needs startpos or fen".to_string()),
+    };
+
+    let mut game = Game::new(position);
+    for uci in words.iter().skip(moves_at + 1) {
+        let played = match Move::from_uci(game.position(), uci) {
+            Some(mv) => game.make_move(mv).map_err(|err| err.to_string()),
+            None => Err(format!("illegal move {}", uci)),
+        };
+        if let Err(err) = played {
+            return Ok((game, Some(err)));
+        }
+    }
+    Ok((game, None))
+}
+
+/// `go` arguments, the clock ones are turned into a fixed time for this move.
+fn parse_limits(words: Vec<&str>, side: PieceColor) -> SearchLimits {
+    let value = |name: &str| -> Option<u64> {
+        let at = words.iter().position(|&word| word == name)?;
+        words.get(at + 1)?.parse().ok()
+    };
+    let mut limits = SearchLimits {
+        depth: value("depth").map(|depth| depth as u32),
+        nodes: value("nodes"),
+        time: value("movetime").map(Duration::from_millis),
+    };
+    if words.contains(&"infinite") {
+        return limits;
+    }
+
+    let (time, increment) = match side {
+        PieceColor::White => (value("wtime"), value("winc")),
+        PieceColor::Black => (value("btime"), value("binc")),
+    };
+    if let (Some(time), None) = (time, limits.time) {
+        let moves_to_go = value("movestogo").unwrap_or(MOVES_TO_GO as u64).max(1);
+        let budget = time / moves_to_go + increment.unwrap_or(0) / 2;
+        limits.time = Some(Duration::from_millis(budget.clamp(1, (time / 2).max(1))));
+    }
+    limits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positions() {
+        let (game, illegal) =
+            parse_position("startpos moves e2e4 e7e5".split(' ').collect()).unwrap();
+        assert_eq!(game.moves().count(), 2);
+        assert_eq!(illegal, None);
+
+        let fen = "4k3/8/8/8/8/8/8/4K2R w K - 0 1";
+        let words = format!("fen {} moves e1g1", fen);
+        let (game, _) = parse_position(words.split(' ').collect()).unwrap();
+        assert_eq!(game.position().to_fen(), "4k3/8/8/8/8/8/8/5RK1 b - - 1 1");
+
+        // the moves before an illegal one still count
+        let (game, illegal) =
+            parse_position("startpos moves d2d4 e7e9 e7e5".split(' ').collect()).unwrap();
+        assert_eq!(game.moves().count(), 1);
+        assert_eq!(illegal.as_deref(), Some("illegal move e7e9"));
+        assert!(parse_position(vec![]).is_err());
+    }
+
+    #[test]
+    fn limits() {
+        let parse = |go: &str, side| parse_limits(go.split(' ').collect(), side);
+        assert_eq!(parse("depth 5", PieceColor::White), SearchLimits::depth(5));
+        assert_eq!(
+            parse("movetime 250", PieceColor::Black),
+            SearchLimits::time(Duration::from_millis(250))
+        );
+        assert_eq!(
+            parse("wtime 60000 btime 1000 winc 2000 binc 0", PieceColor::White),
+            SearchLimits::time(Duration::from_millis(3000))
+        );
+        assert_eq!(
+            parse("wtime 60000 btime 1000 movestogo 1", PieceColor::Black),
+            SearchLimits::time(Duration::from_millis(500))
+        );
+        assert_eq!(
+            parse("infinite", PieceColor::White),
+            SearchLimits::default()
+        );
+    }
+}
@@ -0,0 +1,123 @@
+//! Zobrist keys: a random number for every (piece, square), the side to move, each set of
+//! castling rights and each en passant file. A position's hash is the xor of the keys of
+//! everything in it, so a move only has to xor in and out what it changes.
+
+use crate::piece::{Piece, PieceColor};
+use crate::position::Position;
+use crate::square::Square;
+
+/// splitmix64, fixed seed so hashes are the same from run to run.
+const fn random_keys<const N: usize>(mut seed: u64) -> [u64; N] {
+    let mut keys = [0; N];
+    let mut i = 0;
+    while i < N {
+        seed = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = seed;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        keys[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    keys
+}
+
+/// Indexed by `(color * 6 + piece type) * 64 + square`.
+const PIECE_KEYS: [u64; 768] = random_keys(1);
+const CASTLING_KEYS: [u64; 16] = random_keys(2);
+const EN_PASSANT_KEYS: [u64; 8] = random_keys(3);
+const BLACK_TO_MOVE_KEY: u64 = random_keys::<1>(4)[0];
+
+pub(crate) fn piece(piece: Piece, square: Square) -> u64 {
+    PIECE_KEYS[(piece.color.index() * 6 + piece.piece_type.index()) * 64 + square.index()]
+}
+
+/// `rights` is the four castling rights as bits, see `CastlingRights::bits`.
+pub(crate) fn castling(rights: usize) -> u64 {
+    CASTLING_KEYS[rights]
+}
+
+pub(crate) fn en_passant(square: Square) -> u64 {
+    EN_PASSANT_KEYS[square.file() as usize]
+}
+
+pub(crate) fn side_to_move(color: PieceColor) -> u64 {
+    match color {
+        PieceColor::White => 0,
+        PieceColor::Black => BLACK_TO_MOVE_KEY,
+    }
+}
+
+/// The hash of `position` worked out from scratch, what `Position::hash` keeps up to date.
+pub fn hash(position: &Position) -> u64 {
+    let mut hash = position
+        .pieces()
+        .fold(0, |hash, (square, p)| hash ^ piece(p, square));
+    hash ^= castling(position.castling().bits());
+    hash ^= side_to_move(position.side_to_move());
+    if let Some(square) = position.en_passant_capture() {
+        hash ^= en_passant(square);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Plays every line `depth` moves deep checking the kept hash against a fresh one.
+    fn walk(position: &Position, depth: u32) {
+        assert_eq!(position.hash(), hash(position), "{}", position.to_fen());
+        if depth == 0 {
+            return;
+        }
+        for mv in position.legal_moves() {
+            let mut next = *position;
+            next.make_move(mv);
+            walk(&next, depth - 1);
+        }
+    }
+
+    #[test]
+    fn kept_up_to_date() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        ] {
+            walk(&Position::from_fen(fen).unwrap(), 3);
+        }
+    }
+
+    #[test]
+    fn identifies_positions() {
+        let hash_of = |fen: &str| Position::from_fen(fen).unwrap().hash();
+        let start = Position::start().hash();
+        assert_eq!(
+            hash_of("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+            start
+        );
+        // the move counters don't make a different position
+        assert_eq!(
+            hash_of("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 12 40"),
+            start
+        );
+        assert_ne!(
+            hash_of("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1"),
+            start
+        );
+        assert_ne!(
+            hash_of("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w Kkq - 0 1"),
+            start
+        );
+
+        // an en passant square only counts when a pawn can actually take there
+        assert_eq!(
+            hash_of("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"),
+            hash_of("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1")
+        );
+        assert_ne!(
+            hash_of("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"),
+            hash_of("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1")
+        );
+    }
+}
@@ -0,0 +1,29 @@
+#!/bin/sh
+# A stand-in UCI engine for tests: it answers the handshake and plays its arguments as its
+# moves, one per `go`, then `(none)`. Every command it gets is appended to $FAKE_ENGINE_LOG when
+# that is set.
+while read -r line; do
+    [ -n "$FAKE_ENGINE_LOG" ] && echo "$line" >> "$FAKE_ENGINE_LOG"
+    case "$line" in
+        uci)
+            echo "id name Fake Engine"
+            echo "id author tests"
+            echo "uciok"
+            ;;
+        isready)
+            echo "readyok"
+            ;;
+        go*)
+            echo "info depth 1 score cp 0"
+            if [ $# -gt 0 ]; then
+                echo "bestmove $1"
+                shift
+            else
+                echo "bestmove (none)"
+            fi
+            ;;
+        quit)
+            exit 0
+            ;;
+    esac
+done
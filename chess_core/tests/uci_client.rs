@@ -0,0 +1,70 @@
+use chess_core::uci::{UciEngine, UciError};
+use chess_core::{Game, Move};
+use std::time::Duration;
+
+const MOVETIME: Duration = Duration::from_millis(10);
+
+fn fake_engine(moves: &[&str]) -> UciEngine {
+    let script = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/fake_engine.sh");
+    let mut args = vec![script];
+    args.extend_from_slice(moves);
+    UciEngine::spawn("sh", &args).unwrap()
+}
+
+#[test]
+fn handshake() {
+    let engine = fake_engine(&[]);
+    assert_eq!(engine.name.as_deref(), Some("Fake Engine"));
+}
+
+#[test]
+fn plays_moves() {
+    let mut engine = fake_engine(&["e7e5", "b8c6"]);
+    engine.new_game().unwrap();
+    let mut game = Game::default();
+    for (ours, expected) in [("e2e4", "e7e5"), ("g1f3", "b8c6")] {
+        let mv = Move::from_uci(game.position(), ours).unwrap();
+        game.make_move(mv).unwrap();
+        let reply = engine.best_move(&game, MOVETIME).unwrap().unwrap();
+        assert_eq!(reply.to_uci(), expected);
+        game.make_move(reply).unwrap();
+    }
+    assert_eq!(engine.best_move(&game, MOVETIME).unwrap(), None);
+}
+
+#[test]
+fn rejects_illegal_moves() {
+    let mut engine = fake_engine(&["e2e5"]);
+    assert!(matches!(
+        engine.best_move(&Game::default(), MOVETIME),
+        Err(UciError::IllegalMove(mv)) if mv == "e2e5"
+    ));
+}
+
+#[test]
+fn sends_the_game() {
+    let log = std::env::temp_dir().join(format!("fake_engine_{}.log", std::process::id()));
+    let _ = std::fs::remove_file(&log);
+    {
+        let script = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/fake_engine.sh");
+        let log_var = format!("FAKE_ENGINE_LOG={}", log.display());
+        let mut engine = UciEngine::spawn("env", &[&log_var, "sh", script, "e7e5"]).unwrap();
+        let mut game = Game::default();
+        let mv = Move::from_uci(game.position(), "d2d4").unwrap();
+        game.make_move(mv).unwrap();
+        engine.best_move(&game, MOVETIME).unwrap();
+    }
+
+    let commands = std::fs::read_to_string(&log).unwrap();
+    let _ = std::fs::remove_file(&log);
+    assert_eq!(
+        commands.lines().collect::<Vec<&str>>(),
+        [
+            "uci",
+            "isready",
+            "position startpos moves d2d4",
+            "go movetime 10",
+            "quit"
+        ]
+    );
+}
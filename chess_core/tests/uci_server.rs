@@ -0,0 +1,98 @@
+//! Replays recorded GUI sessions against the UCI server. Transcript lines starting with `>` are
+//! sent to the engine, `<` lines are what it has to answer before the next command goes out. An
+//! expected line ending in `...` only has to match up to there, and `info` lines are skipped
+//! unless one is expected.
+
+use chess_core::uci_server::UciServer;
+use std::io::{self, Write};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::Duration;
+
+/// Hands every line the server writes over to the test.
+struct LineSender {
+    buffer: Vec<u8>,
+    lines: Sender<String>,
+}
+
+impl Write for LineSender {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        while let Some(end) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line = self.buffer.drain(..=end).collect::<Vec<u8>>();
+            let line = String::from_utf8_lossy(&line).trim_end().to_string();
+            let _ = self.lines.send(line);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn expect(lines: &Receiver<String>, expected: &str, name: &str) {
+    let line = loop {
+        let line = lines
+            .recv_timeout(Duration::from_secs(30))
+            .unwrap_or_else(|_| panic!("{}: no answer, expected {:?}", name, expected));
+        if !line.starts_with("info") || expected.starts_with("info") {
+            break line;
+        }
+    };
+    let matches = match expected.strip_suffix("...") {
+        Some(prefix) => line.starts_with(prefix),
+        None => line == expected,
+    };
+    assert!(matches, "{}: expected {:?}, got {:?}", name, expected, line);
+}
+
+fn replay(name: &str) {
+    let path = format!("{}/tests/transcripts/{}", env!("CARGO_MANIFEST_DIR"), name);
+    let transcript = std::fs::read_to_string(path).unwrap();
+    let (sender, lines) = channel();
+    let mut server = UciServer::new(LineSender {
+        buffer: Vec::new(),
+        lines: sender,
+    });
+
+    for line in transcript.lines() {
+        if let Some(command) = line.strip_prefix("> ") {
+            server.handle(command);
+        } else if let Some(expected) = line.strip_prefix("< ") {
+            expect(&lines, expected, name);
+        }
+    }
+    assert!(!server.handle("quit"));
+    let unexpected = lines.try_iter().find(|line| !line.starts_with("info"));
+    assert_eq!(unexpected, None, "{}: unexpected output", name);
+}
+
+#[test]
+fn handshake() {
+    replay("handshake.txt");
+}
+
+#[test]
+fn mate_in_one() {
+    replay("mate_in_one.txt");
+}
+
+#[test]
+fn game_from_moves() {
+    replay("game_from_moves.txt");
+}
+
+#[test]
+fn stop_infinite_search() {
+    replay("stop.txt");
+}
+
+#[test]
+fn moves_up_to_an_illegal_one() {
+    replay("illegal_move.txt");
+}
+
+#[test]
+fn position_stops_a_search() {
+    replay("position_during_search.txt");
+}